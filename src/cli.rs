@@ -1,17 +1,26 @@
 use crate::{
     config::ConfigFile,
+    github_requests::set_offline,
     handlers::{
-        self, InstallResult, erase_handler, list_handler, list_remote_handler, rollback_handler,
-        run_handler, sync_handler, uninstall_handler, update_handler,
+        self, InstallResult, alias_handler, cache_handler, erase_handler,
+        install_handler::{ArchiveSource, BuildOptions},
+        list_handler, list_remote_handler, rollback_handler, run_handler, sync_handler,
+        uninstall_handler, update_handler,
+    },
+    helpers::{
+        directories,
+        lock::{FileLock, LockMode},
+        platform::Platform,
+        processes::is_neovim_running,
     },
-    helpers::processes::is_neovim_running,
     version::parse_version_type,
 };
 use anyhow::Result;
-use clap::{Args, CommandFactory, Parser, ValueEnum};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::shells;
 use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
 use reqwest::{Client, Error};
+use std::{path::PathBuf, time::Duration};
 use tracing::info;
 
 /// Creates a new `reqwest::Client` with default headers.
@@ -51,16 +60,39 @@ fn create_reqwest_client() -> Result<Client, Error> {
     Ok(client)
 }
 
-// The `Cli` enum represents the different commands that can be used in the command-line interface.
+// The `Cli` struct represents the command-line interface, its global flags and the command to run.
 #[derive(Debug, Parser)]
 #[command(version)]
-enum Cli {
+struct Cli {
+    /// Never access the network, only already installed versions can be used
+    #[arg(long, global = true)]
+    offline: bool,
+
+    /// Seconds to wait for another bob process to release the downloads directory
+    #[arg(long, global = true, value_name = "SECONDS", default_value_t = 60)]
+    lock_timeout: u64,
+
+    /// Fail immediately instead of waiting when another bob process is using the downloads directory
+    #[arg(long, global = true, conflicts_with = "lock_timeout")]
+    no_wait: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+// The `Command` enum represents the different commands that can be used in the command-line interface.
+#[derive(Debug, Subcommand)]
+enum Command {
     /// Switch to the specified version, by default will auto-invoke
     /// install command if the version is not installed already
     Use {
         /// Version to switch to |nightly|stable|<version-string>|<commit-hash>|
         ///
         /// A version-string can either be `vx.x.x` or `x.x.x` examples: `v0.6.1` and `0.6.0`
+        ///
+        /// A partial version or semver range such as `0.10`, `~0.9` or `>=0.9.5` picks the newest matching release
+        ///
+        /// `stable~N` picks the release N releases before stable and `previous` the previously used version
         version: String,
 
         /// Whether not to auto-invoke install command
@@ -70,11 +102,83 @@ enum Cli {
 
     /// Install the specified version, can also be used to update
     /// out-of-date nightly version
+    #[command(group = clap::ArgGroup::new("archive").args(["from_file", "from_url", "from_checkout"]).multiple(false))]
     Install {
         /// Version to be installed |nightly|stable|<version-string>|<commit-hash>|
         ///
         /// A version-string can either be `vx.x.x` or `x.x.x` examples: `v0.6.1` and `0.6.0`
-        version: String,
+        ///
+        /// A partial version or semver range such as `0.10`, `~0.9` or `>=0.9.5` picks the newest matching release
+        ///
+        /// `stable~N` picks the release N releases before stable and `previous` the previously used version
+        ///
+        /// `github:<owner>/<repo>[@<ref>]`, `pr:<number>` and `git+<url>[@<ref>]` build a fork, pull request or any git ref from source
+        #[arg(required_unless_present_any = ["from_file", "from_url", "from_checkout"])]
+        version: Option<String>,
+
+        /// Install a Neovim release archive from a local file instead of downloading it
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["version", "from_url"])]
+        from_file: Option<PathBuf>,
+
+        /// Install a Neovim release archive from an arbitrary URL, requires `--as`
+        #[arg(
+            long,
+            value_name = "URL",
+            conflicts_with = "version",
+            requires = "name"
+        )]
+        from_url: Option<String>,
+
+        /// Build a local Neovim checkout, including uncommitted changes, requires `--as`
+        ///
+        /// The checkout is built in a directory of bob's, its own `build` directory is left alone
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with_all = ["version", "sha256"],
+            requires = "name"
+        )]
+        from_checkout: Option<PathBuf>,
+
+        /// Name to install the archive or checkout as, either an exact version or a custom name such as `ci-build`
+        ///
+        /// Defaults to the version reported by the archive's `nvim --version`
+        #[arg(long = "as", value_name = "NAME", requires = "archive")]
+        name: Option<String>,
+
+        /// Expected SHA-256 of the archive given with `--from-file` or `--from-url`
+        #[arg(long, value_name = "HASH", requires = "archive")]
+        sha256: Option<String>,
+
+        /// Install the release for another platform, for example `linux-arm64`, `linux-x86_64`, `macos-arm64`, `macos-x86_64` or `windows-x86_64`
+        ///
+        /// Defaults to the platform of this machine. Releases for another platform are kept in `platforms/<platform>` in the downloads directory and can't be used
+        #[arg(long, value_name = "PLATFORM", conflicts_with = "archive")]
+        platform: Option<Platform>,
+
+        /// Build the version from source instead of downloading its official build, for example to get debug symbols
+        #[arg(long, conflicts_with_all = ["archive", "platform"])]
+        from_source: bool,
+
+        /// Apply a patch file to the source before it is built, can be given more than once
+        ///
+        /// Implies `--from-source` for releases and nightly. Patches configured for the version in `patches` are applied first
+        #[arg(
+            long = "patch",
+            value_name = "PATH",
+            conflicts_with_all = ["archive", "platform"]
+        )]
+        patches: Vec<PathBuf>,
+
+        /// Build with a build profile from `build_profiles` in the config, for example `asan`
+        ///
+        /// Implies `--from-source` for releases and nightly, and can also be used with `--from-checkout`
+        #[arg(
+            long,
+            value_name = "NAME",
+            conflicts_with_all = ["from_file", "from_url", "platform"]
+        )]
+        profile: Option<String>,
     },
 
     /// If `Config::version_sync_file_location` is set, the version in that file
@@ -88,6 +192,10 @@ enum Cli {
         ///
         /// A version-string can either be `vx.x.x` or `x.x.x` examples: `v0.6.1` and `0.6.0`
         ///
+        /// A partial version or semver range such as `0.10`, `~0.9` or `>=0.9.5` picks the newest matching release
+        ///
+        /// `stable~N` picks the release N releases before stable and `previous` the previously used version
+        ///
         /// If no Version is provided a prompt is used to select the versions to be uninstalled
         version: Option<String>,
     },
@@ -124,20 +232,50 @@ enum Cli {
         #[arg(allow_hyphen_values = true)]
         args: Vec<String>,
     },
+
+    /// Manage user-defined version aliases
+    Alias {
+        #[command(subcommand)]
+        command: AliasCommand,
+    },
+
+    /// Manage the local archive cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
 }
 
-impl Cli {
+impl Command {
     /// Utility method to determine if the current command needs a running neovim check.
     /// Only used at the start of the program.
     fn needs_running_check(&self) -> bool {
         matches!(
             self,
-            Cli::Use { .. }
-                | Cli::Install { .. }
-                | Cli::Sync
-                | Cli::Uninstall { .. }
-                | Cli::Rollback
-                | Cli::Update(_)
+            Command::Use { .. }
+                | Command::Install { .. }
+                | Command::Sync
+                | Command::Uninstall { .. }
+                | Command::Rollback
+                | Command::Update(_)
+        )
+    }
+
+    /// Utility method to determine if the current command modifies the downloads directory
+    /// and therefore has to hold the downloads lock.
+    fn needs_lock(&self) -> bool {
+        matches!(
+            self,
+            Command::Use { .. }
+                | Command::Install { .. }
+                | Command::Sync
+                | Command::Uninstall { .. }
+                | Command::Rollback
+                | Command::Erase
+                | Command::Update(_)
+                | Command::Cache {
+                    command: CacheCommand::Clean { .. }
+                }
         )
     }
 }
@@ -170,6 +308,63 @@ pub struct Update {
     pub all: bool,
 }
 
+/// Represents the alias subcommands in the CLI.
+///
+/// Aliases are stored in `Config::aliases` and can be used anywhere a version is accepted.
+///
+/// # Variants
+///
+/// * `Set { name, version }` - Creates or updates the alias `name` to point at `version`.
+/// * `Rm { name }` - Removes the alias `name`.
+/// * `List` - Lists all defined aliases.
+#[derive(Debug, Subcommand)]
+pub enum AliasCommand {
+    /// Create or update an alias
+    Set {
+        /// Name of the alias, for example `work` or `plugin-min`
+        name: String,
+
+        /// Version the alias points at |nightly|stable|<version-string>|<commit-hash>|
+        version: String,
+    },
+
+    /// Remove an alias
+    #[clap(visible_alias = "remove")]
+    Rm {
+        /// Name of the alias to remove
+        name: String,
+    },
+
+    /// List all aliases
+    #[clap(visible_alias = "ls")]
+    List,
+}
+
+/// Represents the cache subcommands in the CLI.
+///
+/// The cache is only filled when `Config::enable_archive_cache` is set.
+///
+/// # Variants
+///
+/// * `List` - Lists all cached archives.
+/// * `Clean { version }` - Removes the cached archive of `version`, or every cached archive.
+/// * `Size` - Prints the total size of the cache.
+#[derive(Debug, Subcommand)]
+pub enum CacheCommand {
+    /// List all cached archives
+    #[clap(visible_alias = "ls")]
+    List,
+
+    /// Remove cached archives
+    Clean {
+        /// Only remove the archive of this version, for example `v0.10.0`
+        version: Option<String>,
+    },
+
+    /// Show the total size of the cache
+    Size,
+}
+
 #[derive(Clone, Copy, Debug, ValueEnum)]
 #[allow(clippy::enum_variant_names)]
 enum Shell {
@@ -235,8 +430,10 @@ impl clap_complete::Generator for Shell {
 pub async fn start(config: ConfigFile) -> Result<()> {
     let client = create_reqwest_client()?;
     let cli = Cli::parse();
+    set_offline(cli.offline);
+    let command = cli.command;
 
-    if cli.needs_running_check()
+    if command.needs_running_check()
         && !config.config.ignore_running_instances.unwrap_or(true)
         && is_neovim_running()
     {
@@ -245,20 +442,90 @@ pub async fn start(config: ConfigFile) -> Result<()> {
         ));
     }
 
-    match cli {
-        Cli::Use {
+    let _lock = if command.needs_lock() {
+        let root = directories::get_downloads_directory(&config.config).await?;
+        let mode = if cli.no_wait {
+            LockMode::FailFast
+        } else {
+            LockMode::Wait(Duration::from_secs(cli.lock_timeout))
+        };
+        Some(FileLock::acquire_downloads(&root, mode).await?)
+    } else {
+        None
+    };
+
+    match command {
+        Command::Use {
             version,
             no_install,
         } => {
-            let version = parse_version_type(&client, &version).await?;
+            let version = parse_version_type(&client, &version, &config.config).await?;
 
             handlers::use_handler::start(version, !no_install, &client, config).await?;
         }
-        Cli::Install { version } => {
-            let version = parse_version_type(&client, &version).await?;
-            let tag_name: &str = version.tag_name.as_str();
+        Command::Install {
+            version,
+            from_file,
+            from_url,
+            from_checkout,
+            name,
+            sha256,
+            platform,
+            from_source,
+            patches,
+            profile,
+        } => {
+            let (tag_name, result) = match (version, from_file, from_url, from_checkout) {
+                (Some(version), ..) => {
+                    let version = parse_version_type(&client, &version, &config.config).await?;
+                    let result = match platform.filter(|platform| !platform.is_current()) {
+                        Some(platform) => {
+                            handlers::install_handler::start_for_platform(
+                                &version, &platform, &client, &config,
+                            )
+                            .await?
+                        }
+                        None if from_source || !patches.is_empty() || profile.is_some() => {
+                            let options = BuildOptions { patches, profile };
+                            handlers::install_handler::start_from_source(
+                                &version, &options, &client, &config,
+                            )
+                            .await?
+                        }
+                        None => {
+                            handlers::install_handler::start(&version, &client, &config).await?
+                        }
+                    };
+                    (version.tag_name, result)
+                }
+                (None, _, _, Some(checkout)) => {
+                    let name = name.expect("clap requires --as with --from-checkout");
+                    handlers::install_handler::start_from_checkout(
+                        &checkout,
+                        &name,
+                        profile.as_deref(),
+                        &config,
+                    )
+                    .await?
+                }
+                (None, from_file, from_url, None) => {
+                    let source = match (from_file, from_url) {
+                        (Some(path), _) => ArchiveSource::File(path),
+                        (None, Some(url)) => ArchiveSource::Url(url),
+                        (None, None) => unreachable!("clap requires a version or an archive"),
+                    };
+                    handlers::install_handler::start_from_archive(
+                        &source,
+                        name.as_deref(),
+                        sha256.as_deref(),
+                        &client,
+                        &config,
+                    )
+                    .await?
+                }
+            };
 
-            match handlers::install_handler::start(&version, &client, &config).await? {
+            match result {
                 InstallResult::InstallationSuccess(location) => {
                     info!("{tag_name} has been successfully installed in {location}",);
                 }
@@ -271,27 +538,29 @@ pub async fn start(config: ConfigFile) -> Result<()> {
                 InstallResult::GivenNightlyRollback => (),
             }
         }
-        Cli::Sync => {
+        Command::Sync => {
             info!("Starting sync process");
             sync_handler::start(&client, config).await?;
         }
-        Cli::Uninstall { version } => {
+        Command::Uninstall { version } => {
             info!("Starting uninstallation process");
             uninstall_handler::start(version.as_deref(), config.config).await?;
         }
-        Cli::Rollback => rollback_handler::start(config.config).await?,
-        Cli::Erase => erase_handler::start(config.config).await?,
-        Cli::List => list_handler::start(config.config).await?,
-        Cli::Complete { shell } => {
+        Command::Rollback => rollback_handler::start(config.config).await?,
+        Command::Erase => erase_handler::start(config.config).await?,
+        Command::List => list_handler::start(config.config).await?,
+        Command::Complete { shell } => {
             clap_complete::generate(shell, &mut Cli::command(), "bob", &mut std::io::stdout());
         }
-        Cli::Update(data) => {
+        Command::Update(data) => {
             update_handler::start(data, &client, config).await?;
         }
-        Cli::ListRemote => list_remote_handler::start(config.config, client).await?,
-        Cli::Run { version, args } => {
+        Command::ListRemote => list_remote_handler::start(config.config, client).await?,
+        Command::Run { version, args } => {
             run_handler::start(&version, &args, &client, &config.config).await?;
         }
+        Command::Alias { command } => alias_handler::start(command, &client, config).await?,
+        Command::Cache { command } => cache_handler::start(command, config.config).await?,
     }
 
     Ok(())
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::{env, path::PathBuf};
+use std::{collections::BTreeMap, env, path::PathBuf};
 use tokio::{
     fs::{self, File},
     io::AsyncWriteExt,
@@ -116,6 +116,13 @@ pub enum ConfigFormat {
 /// * `github_mirror: Option<String>` - The GitHub mirror to use. This is optional and may be `None`.
 /// * `rollback_limit: Option<u8>` - The rollback limit. This is optional and may be `None`.
 /// * `add_neovim_binary_to_path: Option<bool>` - Tells bob whenever to add neovim proxy path to $PATH.
+/// * `aliases: Option<BTreeMap<String, String>>` - User-defined names that resolve to a version string. This is optional and may be `None`.
+/// * `enable_archive_cache: Option<bool>` - Whether to keep downloaded archives in a local cache for reinstalls. This is optional and may be `None`.
+/// * `max_cache_size: Option<u64>` - The maximum size of the archive cache in megabytes. This is optional and may be `None`.
+/// * `source_build_fallback: Option<bool>` - Whether to build a release from source when its official build can't run on this system. This is optional and may be `None`.
+/// * `git_remote: Option<String>` - The git repository to build Neovim from instead of the official one, for example a corporate mirror. This is optional and may be `None`.
+/// * `patches: Option<BTreeMap<String, Vec<String>>>` - Patch files to apply when a version is built from source, keyed by the version they apply to. This is optional and may be `None`.
+/// * `build_profiles: Option<BTreeMap<String, BuildProfile>>` - Named settings for source builds, selected with `bob install --profile`. This is optional and may be `None`.
 ///
 /// # Example
 ///
@@ -129,6 +136,13 @@ pub enum ConfigFormat {
 ///     github_mirror: Some("https://github.com".to_string()),
 ///     rollback_limit: Some(5),
 ///     rollback_limit: Some(true),
+///     aliases: Some(BTreeMap::from([("work".to_string(), "v0.10.0".to_string())])),
+///     enable_archive_cache: Some(true),
+///     max_cache_size: Some(1024),
+///     source_build_fallback: Some(false),
+///     git_remote: Some("https://git.example.com/mirrors/neovim.git".to_string()),
+///     patches: Some(BTreeMap::from([("nightly".to_string(), vec!["/path/to/fix.diff".to_string()])])),
+///     build_profiles: Some(BTreeMap::from([("debug".to_string(), BuildProfile::default())])),
 /// };
 /// println!("The configuration is {:?}", config);
 /// ```
@@ -152,6 +166,73 @@ pub struct Config {
     pub add_neovim_binary_to_path: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ignore_running_instances: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable_archive_cache: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_cache_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_build_fallback: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_remote: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patches: Option<BTreeMap<String, Vec<String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_profiles: Option<BTreeMap<String, BuildProfile>>,
+}
+
+/// Represents a named set of settings for building Neovim from source.
+///
+/// Every setting is optional, settings that aren't given keep the default of a source build.
+///
+/// # Fields
+///
+/// * `build_type: Option<String>` - The CMake build type, such as `Debug`, overriding `Config::enable_release_build`.
+/// * `cmake_flags: Option<Vec<String>>` - Extra flags passed to CMake, such as `-DENABLE_ASAN_UBSAN=ON`.
+/// * `compiler: Option<String>` - The C compiler to build with, such as `clang` or `gcc`.
+/// * `c_flags: Option<String>` - The `CMAKE_C_FLAGS` to build with, such as `-fsanitize=address`.
+/// * `jobs: Option<u32>` - The number of parallel build jobs.
+/// * `bundled_deps: Option<bool>` - Whether to build the bundled dependencies, `false` uses the ones installed on the system.
+///
+/// # Example
+///
+/// ```rust
+/// let profile = BuildProfile {
+///     build_type: Some("Debug".to_string()),
+///     cmake_flags: Some(vec!["-DENABLE_ASAN_UBSAN=ON".to_string()]),
+///     compiler: Some("clang".to_string()),
+///     c_flags: None,
+///     jobs: Some(8),
+///     bundled_deps: Some(true),
+/// };
+/// assert_eq!(profile.cmake_flags()[0], "-DENABLE_ASAN_UBSAN=ON");
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BuildProfile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cmake_flags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compiler: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub c_flags: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bundled_deps: Option<bool>,
+}
+
+impl BuildProfile {
+    /// Returns the extra flags the profile passes to CMake when Neovim is configured, including its `CMAKE_C_FLAGS`.
+    pub fn cmake_flags(&self) -> Vec<String> {
+        let mut flags = self.cmake_flags.clone().unwrap_or_default();
+        if let Some(c_flags) = &self.c_flags {
+            flags.push(format!("-DCMAKE_C_FLAGS={c_flags}"));
+        }
+        flags
+    }
 }
 
 // Going to leave this as a manual implementation for now, unless I can
@@ -169,6 +250,13 @@ impl Default for Config {
             rollback_limit: None,
             add_neovim_binary_to_path: None,
             ignore_running_instances: None,
+            aliases: None,
+            enable_archive_cache: None,
+            max_cache_size: None,
+            source_build_fallback: None,
+            git_remote: None,
+            patches: None,
+            build_profiles: None,
         }
     }
 }
@@ -180,38 +268,46 @@ trait EnvVarProcessor {
     fn process(&mut self) -> Result<()>;
 }
 
-impl EnvVarProcessor for Option<String> {
-    /// `process` method for `Option<String>`.
+impl EnvVarProcessor for String {
+    /// `process` method for `String`.
     /// This is a method for structs that implement the `EnvVarProcessor` trait.
     ///
-    /// It's deigned to process the `Option<String>` type, checking if it contains a value that
+    /// It's deigned to process the `String` type, checking if it contains a value that
     /// matches the `ENVIRONMENT_VAR_REGEX`.
     ///
     /// # Arguments
     ///
-    /// * `&mut self` - A mutable reference to the `Option<String>` instance.
+    /// * `&mut self` - A mutable reference to the `String` instance.
     ///
     /// # Returns
     ///
     /// * `Result<()>` - Returns `Ok(())` if the processing is successful. Error cases include when the environment variable cannot be found or if the regex fails to match.
     fn process(&mut self) -> Result<()> {
-        if let Some(value) = self {
-            if ENVIRONMENT_VAR_REGEX.is_match(value) {
-                let mut extract = ENVIRONMENT_VAR_REGEX.find(value).map_or("", |m| m.as_str());
+        if ENVIRONMENT_VAR_REGEX.is_match(self) {
+            let mut extract = ENVIRONMENT_VAR_REGEX.find(self).map_or("", |m| m.as_str());
 
-                if extract.chars().count() >= 2 && extract.starts_with('$') {
-                    extract = &extract[1..];
-                }
+            if extract.chars().count() >= 2 && extract.starts_with('$') {
+                extract = &extract[1..];
+            }
 
-                let var = env::var(extract).expect("Failed to get environment variable");
+            let var = env::var(extract).expect("Failed to get environment variable");
 
-                *value = value.replace(&format!("${extract}"), &var);
-            }
+            *self = self.replace(&format!("${extract}"), &var);
         }
         Ok(())
     }
 }
 
+impl EnvVarProcessor for Option<String> {
+    /// `process` method for `Option<String>`, processes the contained `String` if there is one.
+    fn process(&mut self) -> Result<()> {
+        match self {
+            Some(value) => value.process(),
+            None => Ok(()),
+        }
+    }
+}
+
 /// Handles environment variables in the configuration.
 ///
 /// This function takes a mutable reference to a `Config` object. It uses a `Regex` to match environment variables in the format `$VARIABLE_NAME`.
@@ -248,7 +344,47 @@ fn handle_envars(config: &mut Config) -> Result<()> {
         &mut config.github_mirror,
         &mut config.installation_location,
         &mut config.version_sync_file_location,
+        &mut config.git_remote,
     ];
 
-    fields.iter_mut().try_for_each(|field| field.process())
+    fields.iter_mut().try_for_each(|field| field.process())?;
+
+    config
+        .patches
+        .iter_mut()
+        .flat_map(BTreeMap::values_mut)
+        .flatten()
+        .try_for_each(EnvVarProcessor::process)
+}
+
+#[cfg(test)]
+mod build_profile_tests {
+    use super::*;
+
+    #[test]
+    fn build_profiles_are_read_from_the_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [build_profiles.asan]
+            build_type = "Debug"
+            cmake_flags = ["-DENABLE_ASAN_UBSAN=ON"]
+            compiler = "clang"
+            c_flags = "-fsanitize=address -fno-omit-frame-pointer"
+            jobs = 8
+            bundled_deps = false
+            "#,
+        )
+        .unwrap();
+
+        let profile = &config.build_profiles.unwrap()["asan"];
+        assert_eq!(profile.jobs, Some(8));
+        assert_eq!(
+            profile.cmake_flags(),
+            [
+                "-DENABLE_ASAN_UBSAN=ON",
+                "-DCMAKE_C_FLAGS=-fsanitize=address -fno-omit-frame-pointer"
+            ]
+        );
+        assert!(BuildProfile::default().cmake_flags().is_empty());
+    }
 }
@@ -54,50 +54,13 @@ pub static ENVIRONMENT_VAR_REGEX: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r"\$([A-Z_]+)").expect("Failed to compile static ENVIRONMENT_VAR_REGEX")
 });
 
-/// # Unix platform-specific compile time constant for the filetype extension of the Neovim binary extension.
+/// Names of the per-project version files looked up by the Neovim proxy, in order of precedence.
 ///
-/// For Windows, it returns "zip".
-/// For unix, it returns "tar.gz".
+/// The proxy walks up from the current directory and uses the first file it finds.
 ///
 /// # Example
 ///
 /// ```rust
-/// #[cfg(target_family = "unix")]
-/// {
-///   let filetype_ext = FILETYPE_EXT;
-///   assert_eq!(filetype_ext, "tar.gz");
-/// }
-///
-/// #[cfg(target_family = "windows")]
-/// {
-///   let filetype_ext = FILETYPE_EXT;
-///   assert_eq!(filetype_ext, "zip");
-/// }
-///
-/// ```
-#[cfg(target_family = "unix")]
-pub const FILETYPE_EXT: &str = "tar.gz";
-
-/// # Windows platform-specific compile time constant for the filetype extension of the Neovim binary extension.
-///
-/// For Windows, it returns "zip".
-/// For unix, it returns "tar.gz".
-///
-/// # Example
-///
-/// ```rust
-/// #[cfg(target_family = "unix")]
-/// {
-///   let filetype_ext = FILETYPE_EXT;
-///   assert_eq!(filetype_ext, "tar.gz");
-/// }
-///
-/// #[cfg(target_family = "windows")]
-/// {
-///   let filetype_ext = FILETYPE_EXT;
-///   assert_eq!(filetype_ext, "zip");
-/// }
-///
+/// assert_eq!(PROJECT_VERSION_FILES[0], ".nvim-version");
 /// ```
-#[cfg(target_family = "windows")]
-pub const FILETYPE_EXT: &str = "zip";
+pub const PROJECT_VERSION_FILES: [&str; 2] = [".nvim-version", ".bob-version"];
@@ -2,16 +2,73 @@ use anyhow::{Result, anyhow};
 use chrono::{DateTime, Utc};
 use reqwest::Client;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether bob runs with `--offline`, set once at startup by the CLI.
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables offline mode for the rest of the process.
+///
+/// # Arguments
+///
+/// * `offline` - Whether any network access should be refused.
+///
+/// # Example
+///
+/// ```rust
+/// set_offline(true);
+/// assert!(is_offline());
+/// ```
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Returns whether bob runs in offline mode.
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Fails if bob runs in offline mode.
+///
+/// Every code path that is about to access the network calls this first, so `--offline` turns network access into a clear error instead of a timeout.
+///
+/// # Arguments
+///
+/// * `action` - A short description of what needs the network, used in the error message.
+///
+/// # Returns
+///
+/// * `Result<()>` - Returns `Ok(())` when network access is allowed.
+///
+/// # Errors
+///
+/// This function will return an error if offline mode is enabled.
+///
+/// # Example
+///
+/// ```rust
+/// ensure_online("Fetching the latest version")?;
+/// ```
+pub fn ensure_online(action: &str) -> Result<()> {
+    if is_offline() {
+        return Err(anyhow!(
+            "{action} requires network access, but bob is running with --offline"
+        ));
+    }
+
+    Ok(())
+}
 
 /// Represents the version of the upstream software in the GitHub API.
 ///
-/// This struct contains the tag name of the version, the target commitish of the version, and the date and time the version was published.
+/// This struct contains the tag name of the version, the target commitish of the version, the date and time the version was published and the files attached to it.
 ///
 /// # Fields
 ///
 /// * `tag_name: String` - The tag name of the version.
 /// * `target_commitish: Option<String>` - The target commitish of the version. This is optional and may be `None`.
 /// * `published_at: DateTime<Utc>` - The date and time the version was published, represented as a `DateTime<Utc>` object.
+/// * `assets: Vec<ReleaseAsset>` - The files attached to the release. They are only needed to pick the archive to download, so they aren't written to disk.
 ///
 /// # Example
 ///
@@ -20,6 +77,7 @@ use serde::{Deserialize, Serialize, de::DeserializeOwned};
 ///     tag_name: "v1.0.0".to_string(),
 ///     target_commitish: Some("abc123".to_string()),
 ///     published_at: Utc::now(),
+///     assets: Vec::new(),
 /// };
 /// println!("The tag name is {}", upstream_version.tag_name);
 /// println!("The target commitish is {}", upstream_version.target_commitish.unwrap_or_default());
@@ -30,6 +88,53 @@ pub struct UpstreamVersion {
     pub tag_name: String,
     pub target_commitish: Option<String>,
     pub published_at: DateTime<Utc>,
+    #[serde(default, skip_serializing)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+/// Represents a file attached to a release in the GitHub API.
+///
+/// # Fields
+///
+/// * `name: String` - The file name of the asset, for example `nvim-linux-x86_64.tar.gz`.
+/// * `browser_download_url: String` - The URL the asset can be downloaded from.
+/// * `size: u64` - The size of the asset in bytes.
+///
+/// # Example
+///
+/// ```rust
+/// let asset = ReleaseAsset {
+///     name: "shasum.txt".to_string(),
+///     browser_download_url: "https://github.com/neovim/neovim/releases/download/v0.11.0/shasum.txt".to_string(),
+///     size: 1024,
+/// };
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// Represents a tag of the Neovim repository in the GitHub API.
+///
+/// Each tag represents a version of Neovim, and the `name` field represents the name of the version.
+///
+/// # Fields
+///
+/// * `name: String` - The name of the tag, for example `v0.10.0` or `nightly`.
+///
+/// # Example
+///
+/// ```rust
+/// let remote_version = RemoteVersion {
+///     name: "v0.5.0".to_string(),
+/// };
+/// ```
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct RemoteVersion {
+    pub name: String,
 }
 
 /// Represents a repository commit in the GitHub API.
@@ -141,7 +246,7 @@ pub struct ErrorResponse {
 
 /// Asynchronously makes a GitHub API request.
 ///
-/// This function takes a reference to a `Client` and a URL as arguments. It sets the "user-agent" header to "bob" and the "Accept" header to "application/vnd.github.v3+json".
+/// This function takes a reference to a `Client` and a URL as arguments. It fails right away in offline mode, otherwise it sets the "user-agent" header to "bob" and the "Accept" header to "application/vnd.github.v3+json".
 /// It then sends the request and awaits the response. It reads the response body as text and returns it as a `String`.
 ///
 /// # Arguments
@@ -170,6 +275,8 @@ pub async fn make_github_request<T: AsRef<str> + reqwest::IntoUrl>(
     client: &Client,
     url: T,
 ) -> Result<String> {
+    ensure_online(&format!("Requesting {}", url.as_ref()))?;
+
     let response = client
         .get(url)
         .header("user-agent", "bob")
@@ -182,68 +289,109 @@ pub async fn make_github_request<T: AsRef<str> + reqwest::IntoUrl>(
     Ok(response)
 }
 
-/// Fetches the upstream nightly version from the GitHub API.
-///
-/// # Parameters
+/// Asynchronously searches for the stable version of Neovim.
 ///
-/// * `Client` - A reference to a `reqwest::Client` used to make the request.
+/// This function takes a reference to a `Client` as an argument and makes a GitHub API request to get the releases of the Neovim repository.
+/// It then deserializes the response into a vector of `UpstreamVersion`.
+/// It finds the release that has the tag name "stable" and the release that has the same `target_commitish` as the stable release but does not have the tag name "stable".
+/// The function returns the tag name of the found release.
 ///
-/// # Returns
+/// # Arguments
 ///
-///  * `Result<UpstreamVersion>` - A `Result` that contains the `UpstreamVersion` if the request was successful, or an error if it failed.
+/// * `client` - A reference to a `Client` used to make the GitHub API request.
 ///
-/// # Errors
+/// # Returns
 ///
-///  This function will return an error if the request to the GitHub API fails or if the response cannot be deserialized into an `UpstreamVersion`.
+/// This function returns a `Result` that contains a `String` representing the tag name of the stable version if the operation was successful.
+/// If the operation failed, the function returns `Err` with a description of the error.
 ///
 /// # Example
 ///
 /// ```rust,no_run
 /// use reqwest::Client;
-/// use bob::github_requests::get_upstream_nightly;
-///
-/// let upstream_version = get_upstream_nightly(&Client::new());
+/// use bob::github_requests::get_upstream_stable;
+/// let client = Client::new();
+/// let upstream_version = get_upstream_stable(&Client::new()).await?;
 /// assert!(upstream_version.is_ok());
 /// ```
-pub async fn get_upstream_nightly(client: &Client) -> Result<UpstreamVersion> {
+pub async fn get_upstream_stable(client: &Client) -> Result<UpstreamVersion> {
     let response = make_github_request(
         client,
-        "https://api.github.com/repos/neovim/neovim/releases/tags/nightly",
+        "https://api.github.com/repos/neovim/neovim/releases/latest",
     )
     .await?;
 
     deserialize_response(&response)
 }
 
-/// Asynchronously searches for the stable version of Neovim.
+/// Fetches a Neovim release by its tag from the GitHub API.
 ///
-/// This function takes a reference to a `Client` as an argument and makes a GitHub API request to get the releases of the Neovim repository.
-/// It then deserializes the response into a vector of `UpstreamVersion`.
-/// It finds the release that has the tag name "stable" and the release that has the same `target_commitish` as the stable release but does not have the tag name "stable".
-/// The function returns the tag name of the found release.
+/// # Parameters
 ///
-/// # Arguments
+/// * `client: &Client` - The HTTP client used to send the request.
+/// * `tag_name: &str` - The tag of the release, for example `v0.10.0` or `nightly`.
 ///
-/// * `client` - A reference to a `Client` used to make the GitHub API request.
+/// # Returns
+///
+/// * `Result<Option<UpstreamVersion>>` - The release with its assets, or `None` if there is no release with this tag.
+///
+/// # Errors
+///
+/// This function will return an error if the request to the GitHub API fails or the response cannot be deserialized into an `UpstreamVersion`.
+///
+/// # Example
+///
+/// ```rust
+/// let client = Client::new();
+/// if let Some(release) = get_upstream_release(&client, "v0.10.0").await? {
+///     println!("v0.10.0 was published at {}", release.published_at);
+/// }
+/// ```
+pub async fn get_upstream_release(
+    client: &Client,
+    tag_name: &str,
+) -> Result<Option<UpstreamVersion>> {
+    let response = make_github_request(
+        client,
+        format!("https://api.github.com/repos/neovim/neovim/releases/tags/{tag_name}"),
+    )
+    .await?;
+
+    // A missing release is answered with a "Not Found" message rather than an empty release
+    if serde_json::from_str::<ErrorResponse>(&response)
+        .is_ok_and(|error| error.message == "Not Found")
+    {
+        return Ok(None);
+    }
+
+    deserialize_response(&response).map(Some)
+}
+
+/// Fetches the tags of the Neovim repository from the GitHub API.
+///
+/// # Parameters
+///
+/// * `client: &Client` - The HTTP client used to send the request.
 ///
 /// # Returns
 ///
-/// This function returns a `Result` that contains a `String` representing the tag name of the stable version if the operation was successful.
-/// If the operation failed, the function returns `Err` with a description of the error.
+/// * `Result<Vec<RemoteVersion>>` - The tags of the repository, newest first, or an error if the request failed.
+///
+/// # Errors
+///
+/// This function will return an error if the request to the GitHub API fails or if the response cannot be deserialized into a vector of `RemoteVersion` objects.
 ///
 /// # Example
 ///
-/// ```rust,no_run
-/// use reqwest::Client;
-/// use bob::github_requests::get_upstream_stable;
+/// ```rust
 /// let client = Client::new();
-/// let upstream_version = get_upstream_stable(&Client::new()).await?;
-/// assert!(upstream_version.is_ok());
+/// let tags = get_upstream_tags(&client).await?;
+/// println!("Neovim has {} tags", tags.len());
 /// ```
-pub async fn get_upstream_stable(client: &Client) -> Result<UpstreamVersion> {
+pub async fn get_upstream_tags(client: &Client) -> Result<Vec<RemoteVersion>> {
     let response = make_github_request(
         client,
-        "https://api.github.com/repos/neovim/neovim/releases/latest",
+        "https://api.github.com/repos/neovim/neovim/tags?per_page=100",
     )
     .await?;
 
@@ -0,0 +1,106 @@
+use anyhow::{Result, anyhow};
+use reqwest::Client;
+use tracing::info;
+
+use crate::{cli::AliasCommand, config::ConfigFile, helpers::version};
+
+/// Starts the alias handler.
+///
+/// This function adds, removes or lists the user-defined version aliases stored in the configuration file.
+///
+/// # Arguments
+///
+/// * `command` - The alias subcommand to run.
+/// * `client` - The client to use for validating the aliased version.
+/// * `config` - The configuration file to read and update the aliases in.
+///
+/// # Returns
+///
+/// * `Result<()>` - Returns a `Result` that indicates whether the operation was successful or not.
+///
+/// # Errors
+///
+/// This function will return an error if:
+///
+/// * The alias name is a version specifier itself.
+/// * The aliased version cannot be parsed.
+/// * The alias to remove does not exist.
+/// * The configuration file cannot be saved.
+///
+/// # Example
+///
+/// ```rust
+/// let command = AliasCommand::Set { name: "work".to_string(), version: "0.10".to_string() };
+/// let client = Client::new();
+/// let config = ConfigFile::get().await?;
+/// start(command, &client, config).await?;
+/// ```
+pub async fn start(command: AliasCommand, client: &Client, mut config: ConfigFile) -> Result<()> {
+    match command {
+        AliasCommand::Set { name, version } => {
+            if version::is_version_specifier(&name) {
+                return Err(anyhow!(
+                    "{name} is a version specifier and cannot be used as an alias name"
+                ));
+            }
+
+            let is_alias_target = config
+                .config
+                .aliases
+                .as_ref()
+                .is_some_and(|aliases| aliases.contains_key(&version));
+            if is_alias_target {
+                return Err(anyhow!(
+                    "{version} is an alias itself, aliases have to point at a version"
+                ));
+            }
+
+            let parsed = version::parse_version_type(client, &version, &config.config).await?;
+
+            config
+                .config
+                .aliases
+                .get_or_insert_with(Default::default)
+                .insert(name.clone(), version.clone());
+            config.save_to_file().await?;
+
+            info!(
+                "Alias {name} now points at {version} (currently {})",
+                parsed.tag_name
+            );
+        }
+        AliasCommand::Rm { name } => {
+            let removed = config
+                .config
+                .aliases
+                .as_mut()
+                .and_then(|aliases| aliases.remove(&name));
+
+            let Some(version) = removed else {
+                return Err(anyhow!("There is no alias named {name}"));
+            };
+
+            if config.config.aliases.as_ref().is_some_and(|a| a.is_empty()) {
+                config.config.aliases = None;
+            }
+            config.save_to_file().await?;
+
+            info!("Removed alias {name} (was {version})");
+        }
+        AliasCommand::List => {
+            let aliases = config.config.aliases.unwrap_or_default();
+
+            if aliases.is_empty() {
+                info!("There are no aliases defined");
+                return Ok(());
+            }
+
+            let name_max_len = aliases.keys().map(String::len).max().unwrap_or(0);
+            for (name, version) in aliases {
+                println!("{name:<name_max_len$}  {version}");
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use anyhow::{Result, anyhow};
+use indicatif::HumanBytes;
+use tracing::info;
+
+use crate::{cli::CacheCommand, config::Config, helpers::cache};
+
+/// Starts the cache handler.
+///
+/// This function lists, cleans or measures the local archive cache.
+///
+/// # Arguments
+///
+/// * `command` - The cache subcommand to run.
+/// * `config` - The configuration to retrieve the downloads directory from.
+///
+/// # Returns
+///
+/// * `Result<()>` - Returns a `Result` that indicates whether the operation was successful or not.
+///
+/// # Errors
+///
+/// This function will return an error if:
+///
+/// * The cache index cannot be read or written.
+/// * A version to clean is given but isn't cached.
+///
+/// # Example
+///
+/// ```rust
+/// let config = Config::default();
+/// start(CacheCommand::Size, config).await?;
+/// ```
+pub async fn start(command: CacheCommand, config: Config) -> Result<()> {
+    if !cache::is_enabled(&config) {
+        info!("The archive cache is disabled, set `enable_archive_cache` to true to enable it");
+    }
+
+    match command {
+        CacheCommand::List => {
+            let mut entries = cache::read_index(&config).await?;
+            if entries.is_empty() {
+                info!("There are no cached archives");
+                return Ok(());
+            }
+
+            entries.sort_by(|a, b| a.tag_name.cmp(&b.tag_name));
+            let tag_max_len = entries.iter().map(|e| e.tag_name.len()).max().unwrap_or(0);
+            let platform_max_len = entries.iter().map(|e| e.platform.len()).max().unwrap_or(0);
+
+            for entry in entries {
+                println!(
+                    "{:<tag_max_len$}  {:<platform_max_len$}  {:>10}  {}",
+                    entry.tag_name,
+                    entry.platform,
+                    HumanBytes(entry.size).to_string(),
+                    &entry.sha256[..12.min(entry.sha256.len())]
+                );
+            }
+        }
+        CacheCommand::Clean { version } => {
+            let removed = match &version {
+                Some(version) => {
+                    let tag_name = if version.starts_with('v') || version == "nightly" {
+                        version.clone()
+                    } else {
+                        format!("v{version}")
+                    };
+                    let removed =
+                        cache::remove_entries(&config, |entry| entry.tag_name == tag_name).await?;
+                    if removed.is_empty() {
+                        return Err(anyhow!("{tag_name} is not cached"));
+                    }
+                    removed
+                }
+                None => cache::remove_entries(&config, |_| true).await?,
+            };
+
+            let freed: u64 = removed.iter().map(|entry| entry.size).sum();
+            info!(
+                "Removed {} cached archive(s), freed {}",
+                removed.len(),
+                HumanBytes(freed)
+            );
+        }
+        CacheCommand::Size => {
+            let entries = cache::read_index(&config).await?;
+            let total: u64 = entries.iter().map(|entry| entry.size).sum();
+
+            match config.max_cache_size {
+                Some(max_size) => println!(
+                    "{} in {} archive(s), limit {}",
+                    HumanBytes(total),
+                    entries.len(),
+                    HumanBytes(max_size * 1024 * 1024)
+                ),
+                None => println!("{} in {} archive(s)", HumanBytes(total), entries.len()),
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,10 +1,14 @@
 use anyhow::{Result, anyhow};
+use std::path::Path;
 use tokio::fs;
 use tracing::info;
 
 use crate::{
     config::Config,
-    helpers::directories::{self},
+    helpers::{
+        directories::{self},
+        lock::LOCK_FILE,
+    },
 };
 
 /// Starts the erase process based on the provided `Config`.
@@ -15,7 +19,8 @@ use crate::{
 ///
 /// # Behavior
 ///
-/// The function attempts to remove the installation and downloads directories. If successful, it logs a success message. If the directories do not exist, it returns an error.
+/// The function attempts to remove the installation directory and the contents of the downloads directory. If successful, it logs a success message. If there is nothing to remove, it returns an error.
+/// The lock file of the downloads directory is kept, see `remove_downloads`.
 ///
 /// On Windows, it also attempts to remove the Neovim installation path from the registry. If successful, it logs a success message.
 ///
@@ -96,10 +101,7 @@ pub async fn start(config: Config) -> Result<()> {
     } else if fs::remove_dir_all(&installation_dir).await.is_ok() {
         info!("Successfully removed neovim's installation folder");
     }
-    if fs::remove_dir_all(downloads).await.is_ok() {
-        // For some weird reason this check doesn't really work for downloads folder
-        // as it keeps thinking the folder exists, and it runs with no issues even tho the folder
-        // doesn't exist damn...
+    if remove_downloads(&downloads).await? {
         info!("Successfully removed neovim downloads folder");
     } else {
         return Err(anyhow!("There's nothing to erase"));
@@ -107,3 +109,70 @@ pub async fn start(config: Config) -> Result<()> {
 
     Ok(())
 }
+
+/// Removes everything in the downloads directory except its lock file.
+///
+/// This process holds the lock on the lock file and other bob processes may be waiting for it.
+/// Deleting it would let them lock a file that no longer exists while new processes create another one, so the two would run at the same time.
+///
+/// # Arguments
+///
+/// * `downloads` - A reference to the downloads directory.
+///
+/// # Returns
+///
+/// * `Result<bool>` - Returns `true` if anything was removed, `false` if there was nothing to remove.
+///
+/// # Errors
+///
+/// This function will return an error if an entry of the downloads directory cannot be removed.
+///
+/// # Example
+///
+/// ```rust
+/// let removed = remove_downloads(Path::new("/path/to/bob")).await?;
+/// ```
+async fn remove_downloads(downloads: &Path) -> Result<bool> {
+    let Ok(mut entries) = fs::read_dir(downloads).await else {
+        return Ok(false);
+    };
+
+    let mut removed = false;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_name() == LOCK_FILE {
+            continue;
+        }
+
+        if entry.file_type().await?.is_dir() {
+            fs::remove_dir_all(entry.path()).await?;
+        } else {
+            fs::remove_file(entry.path()).await?;
+        }
+        removed = true;
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod erase_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn downloads_are_removed_but_the_lock_file_stays() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("v0.10.4").join("bin"))
+            .await
+            .unwrap();
+        fs::write(root.join("used"), "v0.10.4").await.unwrap();
+        fs::write(root.join(LOCK_FILE), "1").await.unwrap();
+
+        assert!(remove_downloads(root).await.unwrap());
+        assert!(!root.join("v0.10.4").exists());
+        assert!(!root.join("used").exists());
+        assert!(root.join(LOCK_FILE).exists());
+
+        assert!(!remove_downloads(root).await.unwrap());
+    }
+}
@@ -1,20 +1,34 @@
-use crate::config::{Config, ConfigFile};
-use crate::github_requests::{UpstreamVersion, get_commits_for_nightly, get_upstream_nightly};
-use crate::helpers::checksum::sha256cmp;
+use crate::config::{BuildProfile, Config, ConfigFile};
+use crate::github_requests::{
+    self, UpstreamVersion, ensure_online, get_commits_for_nightly, is_offline,
+};
+use crate::helpers::cache::{self, CacheEntry};
+use crate::helpers::checksum::{self, find_checksum};
+use crate::helpers::download::{self, DownloadResponse};
+use crate::helpers::libc;
+use crate::helpers::lock::{self, FileLock, LockMode};
+use crate::helpers::platform::{
+    Platform, ReleaseArchive, guess_release_archive, select_release_archive,
+};
 use crate::helpers::processes::handle_subprocess;
+use crate::helpers::progress::PbWrapper;
+use crate::helpers::unarchive::{self, ArchiveFormat};
+use crate::helpers::version::metadata::{read_metadata, write_metadata};
 use crate::helpers::version::nightly::produce_nightly_vec;
-use crate::helpers::version::types::{LocalVersion, ParsedVersion, VersionType};
-use crate::helpers::{self, directories, filesystem, unarchive};
+use crate::helpers::version::types::{
+    AppliedPatch, GitSource, InstallMetadata, InstallSource, LocalVersion, ParsedVersion,
+    VersionType,
+};
+use crate::helpers::{self, directories, filesystem};
 use anyhow::{Result, anyhow};
-use futures_util::stream::StreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
+use chrono::Utc;
 use reqwest::Client;
 use semver::Version;
-use std::cmp::min;
+use sha2::{Digest, Sha256};
 use std::env;
-use std::fmt::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::time::Duration;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use tokio::{fs, process::Command};
@@ -49,6 +63,8 @@ use super::{InstallResult, PostDownloadVersionType};
 /// * There is a problem handling building from source.
 /// * There is a problem unarchiving the downloaded file.
 /// * There is a problem creating the file `nightly/bob.json`.
+/// * There is a problem recording the install metadata.
+/// * There is a problem moving the staged version into place.
 ///
 /// # Panics
 ///
@@ -62,11 +78,79 @@ use super::{InstallResult, PostDownloadVersionType};
 /// let config = Config::default();
 /// let result = start(&mut version, &client, &config).await;
 /// ```
-#[allow(clippy::too_many_lines)]
 pub async fn start(
     version: &ParsedVersion,
     client: &Client,
     config: &ConfigFile,
+) -> Result<InstallResult> {
+    install(version, None, client, config).await
+}
+
+/// Starts the installation process for a given version, building it from source instead of downloading the official build.
+///
+/// The tag of a release, or the commit of a hash, is checked out in `neovim-git` and built with the same steps as commits are.
+/// The build is installed in the regular version directory and its install metadata records it as a source build.
+///
+/// # Arguments
+///
+/// * `version` - A reference to a `ParsedVersion` object representing the version to be built.
+/// * `options` - A reference to the `BuildOptions` to build the version with.
+/// * `client` - A reference to a `Client` object used for making HTTP requests.
+/// * `config` - A reference to a `Config` object containing the configuration settings.
+///
+/// # Returns
+///
+/// * `Result<InstallResult>` - Returns a `Result` that contains an `InstallResult` enum on success, or an error on failure.
+///
+/// # Errors
+///
+/// This function will return an error in the same cases as `start`, or if the build tools are missing or the build fails.
+///
+/// # Example
+///
+/// ```rust
+/// let version = parse_version_type(&client, "v0.10.2", &config.config).await?;
+/// let result = start_from_source(&version, &BuildOptions::default(), &client, &config).await?;
+/// ```
+pub async fn start_from_source(
+    version: &ParsedVersion,
+    options: &BuildOptions,
+    client: &Client,
+    config: &ConfigFile,
+) -> Result<InstallResult> {
+    install(version, Some(options), client, config).await
+}
+
+/// Represents how a version is built from source.
+///
+/// # Fields
+///
+/// * `patches: Vec<PathBuf>` - Patch files applied to the source before it is built, in order.
+/// * `profile: Option<String>` - The name of the build profile from `Config::build_profiles` to build with.
+///
+/// # Example
+///
+/// ```rust
+/// let options = BuildOptions {
+///     patches: vec![PathBuf::from("fix.diff")],
+///     profile: Some("asan".to_string()),
+/// };
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BuildOptions {
+    pub patches: Vec<PathBuf>,
+    pub profile: Option<String>,
+}
+
+/// Installs a version for this machine, either from its official build or, if `options` are given, from source.
+///
+/// Patches configured for the version in `Config::patches` are applied before any given with the options, and a version with patches is always built from source.
+#[allow(clippy::too_many_lines)]
+async fn install(
+    version: &ParsedVersion,
+    options: Option<&BuildOptions>,
+    client: &Client,
+    config: &ConfigFile,
 ) -> Result<InstallResult> {
     if version.version_type == VersionType::NightlyRollback {
         return Ok(InstallResult::GivenNightlyRollback);
@@ -78,6 +162,9 @@ pub async fn start(
         }
     }
 
+    // Relative patch paths are resolved before the working directory changes
+    let build = resolve_build_options(version, options, &config.config).await?;
+
     let root = directories::get_downloads_directory(&config.config).await?;
 
     env::set_current_dir(&root)?;
@@ -85,29 +172,48 @@ pub async fn start(
 
     let is_version_installed =
         helpers::version::is_version_installed(&version.tag_name, &config.config).await?;
+    let build_changed = is_version_installed
+        && build_changed(&root.join(&version.tag_name), build.as_ref()).await?;
+
+    // Nightly and git sources move, so installing them again updates them
+    if is_version_installed
+        && !build_changed
+        && !matches!(
+            version.version_type,
+            VersionType::Nightly | VersionType::Git
+        )
+    {
+        return Ok(InstallResult::VersionAlreadyInstalled);
+    }
+
+    if version.version_type == VersionType::Local {
+        return Err(anyhow!(
+            "{} is not a release, it can only be installed with --from-file, --from-url or --from-checkout",
+            version.tag_name
+        ));
+    }
 
-    if is_version_installed && version.version_type != VersionType::Nightly {
+    if is_version_installed && is_offline() {
+        warn!("Running offline, skipping the check for nightly updates");
         return Ok(InstallResult::VersionAlreadyInstalled);
     }
 
-    let nightly_version = if version.version_type == VersionType::Nightly {
-        Some(get_upstream_nightly(client).await?)
-    } else {
-        None
-    };
+    let release = fetch_upstream_release(client, version).await?;
 
-    if is_version_installed && version.version_type == VersionType::Nightly {
+    // A missing upstream nightly has nothing to compare against, staging it reports why it is missing
+    if let (true, VersionType::Nightly, Some(upstream_nightly)) = (
+        is_version_installed,
+        &version.version_type,
+        release.version(),
+    ) {
         info!("Looking for nightly updates");
 
-        let upstream_nightly = nightly_version.as_ref().unwrap();
         let local_nightly = helpers::version::nightly::get_local_nightly(&config.config).await?;
 
-        if upstream_nightly.published_at == local_nightly.published_at {
+        if upstream_nightly.published_at == local_nightly.published_at && !build_changed {
             return Ok(InstallResult::NightlyIsUpdated);
         }
 
-        handle_rollback(&config.config).await?;
-
         match config.config.enable_nightly_info {
             Some(boolean) if boolean => {
                 print_commits(client, &local_nightly, upstream_nightly).await?;
@@ -117,73 +223,82 @@ pub async fn start(
         }
     }
 
-    let downloaded_archive = match version.version_type {
-        VersionType::Normal | VersionType::Latest => {
-            download_version(client, version, root, &config.config, false).await
-        }
-        VersionType::Nightly => {
-            if config.config.enable_release_build == Some(true) {
-                handle_building_from_source(version, &config.config).await
-            } else {
-                download_version(client, version, root, &config.config, false).await
-            }
+    let staging = filesystem::prepare_staging(root, &version.tag_name).await?;
+    let staged_version = staging.join(&version.tag_name);
+
+    let platform = Platform::current();
+    let source = match stage_version(
+        version,
+        build.as_ref(),
+        &platform,
+        &release,
+        client,
+        config,
+        &staging,
+    )
+    .await
+    {
+        Ok(source) => source,
+        Err(error) => {
+            filesystem::clean_staging(root, &version.tag_name).await?;
+            return Err(error);
         }
-        VersionType::Hash => handle_building_from_source(version, &config.config).await,
-        VersionType::NightlyRollback => Ok(PostDownloadVersionType::None),
-    }?;
-
-    if let PostDownloadVersionType::Standard(downloaded_archive) = downloaded_archive {
-        if version.semver.is_some() && version.semver.as_ref().unwrap() <= &Version::new(0, 4, 4) {
-            unarchive::start(&downloaded_archive).await?;
-        } else {
-            let downloaded_checksum =
-                download_version(client, version, root, &config.config, true).await?;
-            let archive_path = root.join(format!(
-                "{}.{}",
-                downloaded_archive.file_name, downloaded_archive.file_format
-            ));
+    };
 
-            if let PostDownloadVersionType::Standard(downloaded_checksum) = downloaded_checksum {
-                let checksum_path = root.join(format!(
-                    "{}.{}",
-                    downloaded_checksum.file_name, downloaded_checksum.file_format
-                ));
+    // Whatever fails before the version is swapped into place must not leave the staged version behind
+    let prepared = async {
+        if let VersionType::Nightly = version.version_type {
+            if let Some(nightly_version) = release.version() {
+                let nightly_string = serde_json::to_string(&nightly_version)?;
 
-                let platform = helpers::get_platform_name(version.semver.as_ref());
+                let downloads_dir = staged_version.join("bob.json");
+                let mut json_file = File::create(downloads_dir).await?;
 
-                if !sha256cmp(
-                    &archive_path,
-                    &checksum_path,
-                    &format!("{}.{}", platform, downloaded_archive.file_format),
-                )? {
-                    tokio::fs::remove_file(archive_path).await?;
-                    tokio::fs::remove_file(checksum_path).await?;
-                    return Err(anyhow!("Checksum mismatch!"));
+                if let Err(error) = json_file.write_all(nightly_string.as_bytes()).await {
+                    return Err(anyhow!(
+                        "Failed to create file nightly/bob.json, reason: {error}"
+                    ));
                 }
-
-                info!("Checksum matched!");
-                tokio::fs::remove_file(checksum_path).await?;
-                unarchive::start(&downloaded_archive).await?;
-            } else if let PostDownloadVersionType::None = downloaded_checksum {
-                warn!("No checksum provided, skipping checksum verification");
-                unarchive::start(&downloaded_archive).await?;
             }
         }
-    }
 
-    if let VersionType::Nightly = version.version_type {
-        if let Some(nightly_version) = nightly_version {
-            let nightly_string = serde_json::to_string(&nightly_version)?;
+        let metadata = InstallMetadata {
+            tag_name: version.tag_name.clone(),
+            stable: version.version_type == VersionType::Latest,
+            installed_at: Utc::now(),
+            release: release.into_version(),
+            source,
+        };
+        write_metadata(&staged_version, &metadata).await?;
+
+        // Source builds release the lock while they run, so the version may have been installed or uninstalled meanwhile
+        let is_version_installed =
+            helpers::version::is_version_installed(&version.tag_name, &config.config).await?;
+        if is_version_installed && version.version_type == VersionType::Nightly {
+            prepare_rollback(&config.config, root).await
+        } else {
+            Ok(None)
+        }
+    };
+    let rollback = match prepared.await {
+        Ok(rollback) => rollback,
+        Err(error) => {
+            filesystem::clean_staging(root, &version.tag_name).await?;
+            return Err(error);
+        }
+    };
 
-            let downloads_dir = root.join("nightly").join("bob.json");
-            let mut json_file = File::create(downloads_dir).await?;
+    let destination = root.join(&version.tag_name);
+    if let Err(error) =
+        filesystem::swap_into_place(&staged_version, &destination, rollback.as_deref()).await
+    {
+        filesystem::clean_staging(root, &version.tag_name).await?;
+        return Err(error);
+    }
+    filesystem::clean_staging(root, &version.tag_name).await?;
 
-            if let Err(error) = json_file.write_all(nightly_string.as_bytes()).await {
-                return Err(anyhow!(
-                    "Failed to create file nightly/bob.json, reason: {error}"
-                ));
-            }
-        }
+    if let Some(rollback) = rollback {
+        finish_rollback(&config.config, &rollback).await?;
     }
 
     Ok(InstallResult::InstallationSuccess(
@@ -191,327 +306,1615 @@ pub async fn start(
     ))
 }
 
-/// Asynchronously handles the rollback for the nightly version(s) of Neovim.
+/// Installs a release of Neovim built for another platform.
 ///
-/// This function checks if the nightly version is used and if the rollback limit is not zero.
-/// If these conditions are met, it produces a vector of nightly versions and removes the oldest version if the vector's length is greater than or equal to the rollback limit.
-/// It also handles permissions for older installations of nightly on Unix platforms.
-/// Finally, it creates a rollback by copying the nightly directory to a new directory with the ID of the target commitish and updates the JSON file in the new directory.
+/// The release is downloaded and verified like any other, but it is kept in `platforms/<platform>` inside the downloads directory instead of next to the versions of the host,
+/// so it can be bundled for that platform. Versions installed for another platform can't be used with `bob use`.
 ///
 /// # Arguments
 ///
-/// * `config` - A reference to the configuration object.
+/// * `version` - A reference to a `ParsedVersion` object representing the version to be installed.
+/// * `platform` - The platform to install the version for.
+/// * `client` - A reference to a `Client` object used for making HTTP requests.
+/// * `config` - A reference to a `Config` object containing the configuration settings.
 ///
 /// # Returns
 ///
-/// * `Result<()>` - Returns a `Result` that contains `()` on success, or an error on failure.
+/// * `Result<InstallResult>` - Returns a `Result` that contains an `InstallResult` enum on success, or an error on failure.
 ///
 /// # Errors
 ///
 /// This function will return an error if:
-/// * There is a failure in producing the vector of nightly versions.
-/// * There is a failure in removing the oldest version.
-/// * There is a failure in reading the nightly JSON file.
-/// * There is a failure in parsing the JSON file.
-/// * There is a failure in copying the nightly directory.
-/// * There is a failure in writing the updated JSON file.
+/// * The version is not a release, commits can't be built from source for another platform.
+/// * The version is below 0.2.2.
+/// * The release has no archive for the platform.
+/// * There is a problem downloading or unarchiving the release.
+/// * There is a problem moving the staged version into place.
 ///
 /// # Example
 ///
 /// ```rust
-/// let config = Config::default();
-/// handle_rollback(&config).await?;
-/// `
-async fn handle_rollback(config: &Config) -> Result<()> {
-    if !helpers::version::is_version_used("nightly", config).await {
-        return Ok(());
+/// let platform: Platform = "linux-arm64".parse()?;
+/// let result = start_for_platform(&version, &platform, &client, &config).await?;
+/// ```
+pub async fn start_for_platform(
+    version: &ParsedVersion,
+    platform: &Platform,
+    client: &Client,
+    config: &ConfigFile,
+) -> Result<InstallResult> {
+    if matches!(
+        version.version_type,
+        VersionType::Hash | VersionType::Git | VersionType::Local | VersionType::NightlyRollback
+    ) {
+        return Err(anyhow!(
+            "Only releases can be installed for another platform, {} is not a release",
+            version.tag_name
+        ));
     }
 
-    let rollback_limit = config.rollback_limit.unwrap_or(3);
+    if let Some(version) = &version.semver {
+        if version <= &Version::new(0, 2, 2) {
+            return Err(anyhow!("Versions below 0.2.2 are not supported"));
+        }
+    }
 
-    if rollback_limit == 0 {
-        return Ok(());
+    let root = directories::get_platform_directory(&config.config, platform).await?;
+    let root = root.as_path();
+    let destination = root.join(&version.tag_name);
+    let is_version_installed = fs::metadata(&destination).await.is_ok();
+
+    if is_version_installed && (version.version_type != VersionType::Nightly || is_offline()) {
+        return Ok(InstallResult::VersionAlreadyInstalled);
     }
 
-    let mut nightly_vec = produce_nightly_vec(config).await?;
+    let release = fetch_upstream_release(client, version).await?;
 
-    if nightly_vec.len() >= rollback_limit.into() {
-        let oldest_path = nightly_vec.pop().unwrap().path;
-        fs::remove_dir_all(oldest_path).await?;
+    if let (VersionType::Nightly, Some(upstream_nightly), Some(local_nightly)) = (
+        &version.version_type,
+        release.version(),
+        read_metadata(&destination)
+            .await?
+            .and_then(|metadata| metadata.release),
+    ) {
+        if upstream_nightly.published_at == local_nightly.published_at {
+            return Ok(InstallResult::NightlyIsUpdated);
+        }
     }
 
-    let nightly_file = fs::read_to_string("nightly/bob.json").await?;
-    let mut json_struct: UpstreamVersion = serde_json::from_str(&nightly_file)?;
-    let id: String = json_struct
-        .target_commitish
-        .as_ref()
-        .unwrap()
-        .chars()
-        .take(7)
-        .collect();
+    let staging = filesystem::prepare_staging(root, &version.tag_name).await?;
+    let staged_version = staging.join(&version.tag_name);
 
-    info!("Creating rollback: nightly-{id}");
-    filesystem::copy_dir_async("nightly", format!("nightly-{id}")).await?;
-    let _ = write!(json_struct.tag_name, "-{id}");
+    if let Err(error) =
+        stage_version(version, None, platform, &release, client, config, &staging).await
+    {
+        filesystem::clean_staging(root, &version.tag_name).await?;
+        return Err(error);
+    }
 
-    let json_file = serde_json::to_string(&json_struct)?;
-    fs::write(format!("nightly-{id}/bob.json"), json_file).await?;
+    let metadata = InstallMetadata {
+        tag_name: version.tag_name.clone(),
+        stable: version.version_type == VersionType::Latest,
+        installed_at: Utc::now(),
+        release: release.into_version(),
+        source: None,
+    };
+    write_metadata(&staged_version, &metadata).await?;
 
-    Ok(())
+    let result = filesystem::swap_into_place(&staged_version, &destination, None).await;
+    filesystem::clean_staging(root, &version.tag_name).await?;
+    result?;
+
+    Ok(InstallResult::InstallationSuccess(
+        root.display().to_string(),
+    ))
 }
 
-/// Asynchronously prints the commits between two versions of Neovim.
+/// The outcome of looking up the upstream release of a version being installed.
 ///
-/// This function fetches the commits between the published dates of the local and upstream versions of Neovim.
-/// It then prints each commit with the author's name in blue and the commit message.
+/// # Variants
+///
+/// * `Found(UpstreamVersion)` - The release, with the assets the archive is picked from.
+/// * `Missing` - GitHub has no release with this tag.
+/// * `Unknown` - The release wasn't looked up or the request failed, the official archive names are used instead of its assets.
+#[derive(Debug)]
+enum UpstreamRelease {
+    Found(UpstreamVersion),
+    Missing,
+    Unknown,
+}
+
+impl UpstreamRelease {
+    /// Returns the release, if it was found.
+    fn version(&self) -> Option<&UpstreamVersion> {
+        match self {
+            UpstreamRelease::Found(release) => Some(release),
+            UpstreamRelease::Missing | UpstreamRelease::Unknown => None,
+        }
+    }
+
+    /// Returns the release to record in the install metadata, if it was found.
+    fn into_version(self) -> Option<UpstreamVersion> {
+        match self {
+            UpstreamRelease::Found(release) => Some(release),
+            UpstreamRelease::Missing | UpstreamRelease::Unknown => None,
+        }
+    }
+}
+
+/// Fetches the upstream release of a version, to pick its archive and record it in its install metadata.
+///
+/// The release is requested once, its assets are used to pick the archive to download.
+/// Nightly is needed to look for updates, so failing to fetch it is an error. For other releases a failed request only logs a warning,
+/// and the archive is downloaded by its official name. Offline, commits and local versions, the release isn't looked up.
 ///
 /// # Arguments
 ///
 /// * `client` - A reference to the HTTP client.
-/// * `local` - A reference to the local version of Neovim.
-/// * `upstream` - A reference to the upstream version of Neovim.
+/// * `version` - A reference to the parsed version being installed.
 ///
 /// # Returns
 ///
-/// * `Result<()>` - Returns a `Result` that contains `()` on success, or an error on failure.
+/// * `Result<UpstreamRelease>` - Returns the release, or whether it is missing or unknown.
 ///
 /// # Errors
 ///
-/// This function will return an error if:
-/// * There is a failure in fetching the commits between the published dates of the local and upstream versions.
+/// This function will return an error if the nightly release cannot be fetched.
 ///
 /// # Example
 ///
 /// ```rust
-/// let client = Client::new();
-/// let local = UpstreamVersion::get_local_version();
-/// let upstream = UpstreamVersion::get_upstream_version(&client).await?;
-/// print_commits(&client, &local, &upstream).await?;
+/// let release = fetch_upstream_release(&client, &version).await?;
 /// ```
-async fn print_commits(
+async fn fetch_upstream_release(
     client: &Client,
-    local: &UpstreamVersion,
-    upstream: &UpstreamVersion,
-) -> Result<()> {
-    let commits =
-        get_commits_for_nightly(client, &local.published_at, &upstream.published_at).await?;
+    version: &ParsedVersion,
+) -> Result<UpstreamRelease> {
+    let release = match version.version_type {
+        VersionType::Nightly => github_requests::get_upstream_release(client, "nightly").await?,
+        VersionType::Normal | VersionType::Latest if !is_offline() => {
+            match github_requests::get_upstream_release(client, &version.tag_name).await {
+                Ok(release) => release,
+                Err(error) => {
+                    warn!(
+                        "Couldn't fetch the release {}, falling back to the default asset names: {error}",
+                        version.tag_name
+                    );
+                    return Ok(UpstreamRelease::Unknown);
+                }
+            }
+        }
+        _ => return Ok(UpstreamRelease::Unknown),
+    };
 
-    for commit in commits {
-        println!(
-            "| {} {}\n",
-            Paint::blue(commit.commit.author.name).bold(),
-            commit.commit.message.replace('\n', "\n| ")
-        );
-    }
+    Ok(match release {
+        Some(release) => UpstreamRelease::Found(release),
+        None => UpstreamRelease::Missing,
+    })
+}
 
-    Ok(())
+/// Represents a Neovim release archive that doesn't come from the Neovim releases.
+///
+/// # Variants
+///
+/// * `File(PathBuf)` - An archive on the local disk.
+/// * `Url(String)` - An archive at an arbitrary URL, for example an internal artifact server.
+#[derive(Debug, Clone)]
+pub enum ArchiveSource {
+    File(PathBuf),
+    Url(String),
 }
 
-/// Asynchronously downloads a specified version of Neovim.
+/// Installs a Neovim release archive from a local file or an arbitrary URL.
 ///
-/// This function sends a request to download the specified version of Neovim based on the version type.
-/// If the version type is Normal, Nightly, or Latest, it sends a request to download the version.
-/// If the version type is Hash, it handles building from the source.
-/// If the version type is `NightlyRollback`, it does nothing.
+/// The archive goes through the same staging, verification and extraction as downloaded releases.
+/// If `name` is `None`, the version reported by the extracted `nvim --version` is used as the name.
 ///
 /// # Arguments
 ///
+/// * `source` - A reference to the `ArchiveSource` to install.
+/// * `name` - The name to install the archive as, an exact version such as `0.10.2` is installed as `v0.10.2`.
+/// * `sha256` - The expected hex encoded SHA-256 of the archive, if known.
 /// * `client` - A reference to the HTTP client.
-/// * `version` - A reference to the parsed version of Neovim to be downloaded.
-/// * `root` - A reference to the path where the downloaded file will be saved.
-/// * `config` - A reference to the configuration object.
-/// * `sha256sum` - A boolean indicating whether to get the sha256sum
+/// * `config` - A reference to the configuration file.
 ///
 /// # Returns
 ///
-/// * `Result<PostDownloadVersionType>` - Returns a `Result` that contains a `PostDownloadVersionType` on success, or an error on failure.
+/// * `Result<(String, InstallResult)>` - Returns the name the archive was installed as together with the `InstallResult`.
 ///
 /// # Errors
 ///
 /// This function will return an error if:
-/// * There is a failure in sending the request to download the version.
-/// * The response status is not 200.
-/// * There is a failure in creating the file where the downloaded version will be saved.
-/// * There is a failure in writing the downloaded bytes to the file.
+/// * The name is not a valid custom name or is already used by an alias.
+/// * The archive cannot be read or downloaded.
+/// * The checksum of the archive does not match.
+/// * There is a problem unarchiving the archive.
+/// * No name is given and it cannot be detected from the extracted binary.
+/// * There is a problem moving the staged version into place.
 ///
 /// # Example
 ///
 /// ```rust
-/// let client = Client::new();
-/// let version = ParsedVersion::parse("0.5.0");
-/// let root = Path::new("/path/to/save");
-/// let config = Config::default();
-/// let result = download_version(&client, &version, &root, &config).await;
+/// let source = ArchiveSource::File(PathBuf::from("/mnt/usb/nvim-linux-x86_64.tar.gz"));
+/// let (name, result) = start_from_archive(&source, Some("usb"), None, &client, &config).await?;
 /// ```
-async fn download_version(
+pub async fn start_from_archive(
+    source: &ArchiveSource,
+    name: Option<&str>,
+    sha256: Option<&str>,
     client: &Client,
-    version: &ParsedVersion,
-    root: &Path,
-    config: &Config,
-    get_sha256sum: bool,
-) -> Result<PostDownloadVersionType> {
-    match version.version_type {
-        VersionType::Normal | VersionType::Nightly | VersionType::Latest => {
-            let response = send_request(client, config, version, get_sha256sum).await;
-
-            // Handle error case first so we don't need a match statement
-            let response = if let Err(error) = response {
-                return Err(anyhow!(
-                    "Failed to download version {}: {error}",
-                    version.tag_name
-                ));
-            } else {
-                response?
-            };
-
-            if matches!(response.status(), reqwest::StatusCode::OK) {
-                let total_size = response.content_length().unwrap_or(0);
-                let mut response_bytes = response.bytes_stream();
-
-                let pbw = PbWrapper::new(total_size, &version.tag_name, get_sha256sum);
-
-                let file_type = file_type_ext(version, get_sha256sum);
-
-                let mut file =
-                    tokio::fs::File::create(format!("{}.{file_type}", version.tag_name)).await?;
-                let mut downloaded: u64 = 0;
-
-                while let Some(item) = response_bytes.next().await {
-                    let chunk = item.map_err(|_| anyhow!("hello"))?;
-                    file.write_all(&chunk).await?;
-                    let new = min(downloaded + (chunk.len() as u64), total_size);
-                    downloaded = new;
-                    pbw.set_position(new);
-                }
-
-                file.flush().await?;
-                file.sync_all().await?;
+    config: &ConfigFile,
+) -> Result<(String, InstallResult)> {
+    let name = name
+        .map(|name| parse_install_name(name, &config.config))
+        .transpose()?;
 
-                pbw.finish(root, file_type.clone().into());
+    let root = directories::get_downloads_directory(&config.config).await?;
+    let root = root.as_path();
 
-                Ok(PostDownloadVersionType::Standard(LocalVersion {
-                    file_name: version.tag_name.clone(),
-                    file_format: file_type.to_string(),
-                    path: root.display().to_string(),
-                    semver: version.semver.clone(),
-                }))
-            } else if get_sha256sum {
-                Ok(PostDownloadVersionType::None)
-            } else {
-                let error_text = response.text().await?;
-                if error_text.contains("Not Found") {
-                    return Err(anyhow!(
-                        "Version does not exist in Neovim releases. Please check available versions with 'bob list-remote'"
-                    ));
-                }
-                Err(anyhow!(
-                    "Failed to download version {}: {}",
-                    version.tag_name,
-                    error_text
-                ))
-            }
+    if let Some(name) = &name {
+        if helpers::version::is_version_installed(name, &config.config).await? {
+            return Ok((name.clone(), InstallResult::VersionAlreadyInstalled));
         }
-        VersionType::Hash => handle_building_from_source(version, config).await,
-        VersionType::NightlyRollback => Ok(PostDownloadVersionType::None),
     }
-}
 
-struct PbWrapper<'a, S> {
-    pb: ProgressBar,
-    tag_name: &'a S,
-    download_type: &'static str,
-}
+    // The name may only be known after extraction, so the archive is staged under a fixed key
+    let key = name.clone().unwrap_or_else(|| "archive".to_string());
+    let staging = filesystem::prepare_staging(root, &key).await?;
 
-impl<'a, S> PbWrapper<'_, S>
-where
-    S: AsRef<str>,
-{
-    fn new(total_size: u64, tag_name: &'a S, get_sha256sum: bool) -> PbWrapper<'a, S> {
-        let pb = ProgressBar::new(total_size);
-        pb.set_style(ProgressStyle::with_template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-        .map_err(|_| anyhow!("Failed to set progress bar style")).unwrap()
-        .progress_chars("█  "));
-        let dl = if get_sha256sum { "checksum" } else { "version" };
-        PbWrapper {
-            pb,
-            tag_name,
-            download_type: dl,
-        }
-    }
-
-    fn finish<P>(&self, root: P, file_type: S)
-    where
-        P: AsRef<Path>,
-    {
-        self.pb.finish_with_message(format!(
-            "Downloaded {} {} to {}/{}.{}",
-            self.download_type,
-            self.tag_name.as_ref(),
-            root.as_ref().display(),
-            self.tag_name.as_ref(),
-            file_type.as_ref()
-        ));
-    }
+    let staged = match stage_archive(source, &key, sha256, client, &staging).await {
+        Ok(()) => detect_install_name(&staging.join(&key), name, &config.config).await,
+        Err(error) => Err(error),
+    };
+    let name = match staged {
+        Ok(name) => name,
+        Err(error) => {
+            filesystem::clean_staging(root, &key).await?;
+            return Err(error);
+        }
+    };
 
-    fn set_position(&self, position: u64) {
-        self.pb.set_position(position);
+    if helpers::version::is_version_installed(&name, &config.config).await? {
+        filesystem::clean_staging(root, &key).await?;
+        return Ok((name, InstallResult::VersionAlreadyInstalled));
     }
-}
 
-fn file_type_ext(version: &ParsedVersion, get_sha256sum: bool) -> std::borrow::Cow<'static, str> {
-    if get_sha256sum {
-        if version.version_type == VersionType::Nightly
-            || version.semver.as_ref().unwrap() > &Version::new(0, 10, 4)
-        {
-            std::borrow::Cow::Owned("shasum.txt".to_string())
-        } else {
-            std::borrow::Cow::Owned(format!("{}.sha256sum", crate::FILETYPE_EXT))
-        }
-    } else {
-        std::borrow::Cow::Owned(crate::FILETYPE_EXT.to_string())
-    }
+    let source = match source {
+        ArchiveSource::File(path) => InstallSource::File {
+            path: fs::canonicalize(path)
+                .await
+                .unwrap_or_else(|_| path.clone())
+                .display()
+                .to_string(),
+        },
+        ArchiveSource::Url(url) => InstallSource::Url { url: url.clone() },
+    };
+    let metadata = InstallMetadata {
+        tag_name: name.clone(),
+        stable: false,
+        installed_at: Utc::now(),
+        release: None,
+        source: Some(source),
+    };
+    write_metadata(&staging.join(&key), &metadata).await?;
+
+    let result = filesystem::swap_into_place(&staging.join(&key), &root.join(&name), None).await;
+    filesystem::clean_staging(root, &key).await?;
+    result?;
+
+    Ok((
+        name,
+        InstallResult::InstallationSuccess(root.display().to_string()),
+    ))
 }
 
-/// Asynchronously handles the building of a specified version from source.
+/// Builds a local Neovim checkout and installs it under a custom name.
 ///
-/// This function checks for the presence of necessary tools (like Clang, GCC, Cmake, and Git) in the system.
+/// The working tree is built as it is, including uncommitted changes, with the same steps as other source builds.
+/// The checkout's `build` directory is reused, so building the same checkout again only recompiles what changed.
+/// Installing a name that was built from a checkout before rebuilds it, any other installed version with that name is left alone.
 ///
-/// It then:
+/// # Arguments
+///
+/// * `checkout` - A reference to the path of the Neovim checkout.
+/// * `name` - The name to install the build as, an exact version such as `0.10.2` is installed as `v0.10.2`.
+/// * `profile` - The name of the build profile from `Config::build_profiles` to build with, if any.
+/// * `config` - A reference to the configuration file.
+///
+/// # Returns
+///
+/// * `Result<(String, InstallResult)>` - Returns the name the build was installed as together with the `InstallResult`.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * The name is not a valid custom name, is already used by an alias or by a version that wasn't built from a checkout.
+/// * The path is not a Neovim checkout.
+/// * The build profile isn't defined.
+/// * The build tools are missing or the build fails.
+/// * There is a problem moving the staged version into place.
+///
+/// # Example
+///
+/// ```rust
+/// let (name, result) = start_from_checkout(Path::new("/home/user/src/neovim"), "dev", None, &config).await?;
+/// ```
+pub async fn start_from_checkout(
+    checkout: &Path,
+    name: &str,
+    profile: Option<&str>,
+    config: &ConfigFile,
+) -> Result<(String, InstallResult)> {
+    let name = parse_install_name(name, &config.config)?;
+    if let Some(profile) = profile {
+        build_profile(&config.config, profile)?;
+    }
+
+    let checkout = fs::canonicalize(checkout)
+        .await
+        .map_err(|error| anyhow!("Failed to read {}: {error}", checkout.display()))?;
+    if fs::metadata(checkout.join("CMakeLists.txt")).await.is_err()
+        || fs::metadata(checkout.join("src").join("nvim"))
+            .await
+            .is_err()
+    {
+        return Err(anyhow!("{} is not a Neovim checkout", checkout.display()));
+    }
+
+    let root = directories::get_downloads_directory(&config.config).await?;
+    let root = root.as_path();
+    let destination = root.join(&name);
+
+    if helpers::version::is_version_installed(&name, &config.config).await? {
+        let source = read_metadata(&destination)
+            .await?
+            .and_then(|metadata| metadata.source);
+        if !matches!(source, Some(InstallSource::Checkout { .. })) {
+            return Err(anyhow!(
+                "{name} is already installed and wasn't built from a checkout, pick another name with --as"
+            ));
+        }
+    }
+
+    check_build_tools().await?;
+
+    let staging = filesystem::prepare_staging(root, &name).await?;
+    let staged_version = staging.join(&name);
+
+    env::set_current_dir(&checkout)?;
+    if let Err(error) = build_checkout(&checkout, &staged_version, profile, root, config).await {
+        filesystem::clean_staging(root, &name).await?;
+        return Err(error);
+    }
+
+    let metadata = InstallMetadata {
+        tag_name: name.clone(),
+        stable: false,
+        installed_at: Utc::now(),
+        release: None,
+        source: Some(InstallSource::Checkout {
+            path: checkout.display().to_string(),
+        }),
+    };
+    write_metadata(&staged_version, &metadata).await?;
+
+    let result = filesystem::swap_into_place(&staged_version, &destination, None).await;
+    filesystem::clean_staging(root, &name).await?;
+    result?;
+
+    Ok((
+        name,
+        InstallResult::InstallationSuccess(root.display().to_string()),
+    ))
+}
+
+/// Builds the checkout in the current directory and installs it to `install_dir`, without writing to the checkout.
+///
+/// make always builds in the `build` directory of the source tree, and would change the cmake cache the user configured there.
+/// So the checkout is built with cmake in a directory of its own in `neovim-git/checkouts`, which is kept to rebuild the checkout incrementally,
+/// and its bundled dependencies come from the dependency cache, see `cached_deps`.
+async fn build_checkout(
+    checkout: &Path,
+    install_dir: &Path,
+    profile: Option<&str>,
+    root: &Path,
+    config: &ConfigFile,
+) -> Result<()> {
+    let repo = root.join("neovim-git");
+    let key = Sha256::digest(checkout.to_string_lossy().as_bytes());
+    let build_dir = repo.join("checkouts").join(&format!("{key:x}")[..16]);
+    fs::create_dir_all(&build_dir).await?;
+
+    let (deps, deps_lock) = cached_deps(&repo, &config.config, profile).await?;
+    build_neovim(
+        &config.config,
+        install_dir,
+        profile,
+        &deps,
+        Some(&build_dir),
+    )
+    .await?;
+
+    if let BundledDeps::Build(entry) = &deps {
+        File::create(entry.join(DEPS_READY)).await?;
+    }
+    drop(deps_lock);
+
+    Ok(())
+}
+
+/// Validates the name an archive is installed as, normalizing exact versions to their tag name.
+fn parse_install_name(name: &str, config: &Config) -> Result<String> {
+    if crate::VERSION_REGEX.is_match(name) {
+        let tag_name = if name.starts_with('v') {
+            name.to_string()
+        } else {
+            format!("v{name}")
+        };
+        Version::parse(&tag_name[1..])
+            .map_err(|_| anyhow!("{name} is not an exact version such as v0.10.2"))?;
+        return Ok(tag_name);
+    }
+
+    if !helpers::version::is_valid_install_name(name) {
+        return Err(anyhow!(
+            "{name} can't be used as a name, use an exact version or a name like `ci-build` that isn't a version specifier"
+        ));
+    }
+
+    if config
+        .aliases
+        .as_ref()
+        .is_some_and(|aliases| aliases.contains_key(name))
+    {
+        return Err(anyhow!("{name} is already used by an alias"));
+    }
+
+    Ok(name.to_string())
+}
+
+/// Copies or downloads an archive into the staging directory and expands it to `staging/key`.
+async fn stage_archive(
+    source: &ArchiveSource,
+    key: &str,
+    sha256: Option<&str>,
+    client: &Client,
+    staging: &Path,
+) -> Result<()> {
+    // The format is only known once the archive is on disk, it is detected from its magic bytes
+    let archive_path = staging.join(format!("{key}.download"));
+
+    match source {
+        ArchiveSource::File(path) => {
+            if let Err(error) = fs::copy(path, &archive_path).await {
+                return Err(anyhow!("Failed to read {}: {error}", path.display()));
+            }
+
+            if let Some(expected) = sha256 {
+                if !checksum::hash_file(&archive_path)
+                    .await?
+                    .eq_ignore_ascii_case(expected)
+                {
+                    return Err(anyhow!("Checksum mismatch!"));
+                }
+                info!("Checksum matched!");
+            }
+        }
+        ArchiveSource::Url(url) => {
+            ensure_online(&format!("Downloading {url}"))?;
+
+            let partial = staging.join("partial").join(format!("{key}.download"));
+            match download::request(client, url, &partial).await? {
+                DownloadResponse::Ready(download) => {
+                    let pbw = PbWrapper::new(download.total_size(), format!("Downloading {url}"));
+                    download
+                        .save(&archive_path, sha256, |position| pbw.set_position(position))
+                        .await?;
+                    pbw.finish(format!("Downloaded {url}"));
+                    if sha256.is_some() {
+                        info!("Checksum matched!");
+                    }
+                }
+                DownloadResponse::Failed(response) => {
+                    return Err(anyhow!("Failed to download {url}: {}", response.status()));
+                }
+            }
+        }
+    }
+
+    if sha256.is_none() {
+        warn!("No checksum provided, skipping checksum verification");
+    }
+
+    let format = ArchiveFormat::detect(&archive_path).map_err(|error| match source {
+        ArchiveSource::File(path) => anyhow!("Can't install {}: {error}", path.display()),
+        ArchiveSource::Url(url) => anyhow!("Can't install {url}: {error}"),
+    })?;
+    fs::rename(&archive_path, staging.join(format!("{key}.{format}"))).await?;
+
+    unarchive::start(&LocalVersion {
+        file_name: key.to_string(),
+        file_format: format.to_string(),
+        path: staging.display().to_string(),
+        semver: None,
+    })
+    .await
+}
+
+/// Returns the name to install an expanded archive as.
+///
+/// If no name was given, the version reported by the expanded `nvim --version` is used, which only works for release builds.
+async fn detect_install_name(
+    expanded: &Path,
+    name: Option<String>,
+    config: &Config,
+) -> Result<String> {
+    if let Some(name) = name {
+        return Ok(name);
+    }
+
+    let output = Command::new(expanded.join("bin").join("nvim"))
+        .arg("--version")
+        .output()
+        .await
+        .map_err(|error| anyhow!("Failed to run the archive's nvim to detect its version, pass a name with --as: {error}"))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let reported = stdout
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("NVIM "))
+        .map(str::trim)
+        .unwrap_or_default();
+
+    let name = parse_install_name(reported, config).map_err(|_| {
+        anyhow!("The archive contains Neovim {reported:?}, which is not a release version, pass a name with --as")
+    })?;
+    info!("Detected version {name}");
+    Ok(name)
+}
+
+/// Downloads and expands (or builds) a version into the staging directory.
+///
+/// Nothing outside of `staging` is touched, so an interrupted or failed install never affects the installed versions.
+/// Before an official build is downloaded for this machine, it is checked to run with the C library of the host.
+/// If it doesn't, or the release has no official build for this machine, the version is built from source when `Config::source_build_fallback` is set.
+///
+/// # Arguments
+///
+/// * `version` - A reference to the parsed version to stage.
+/// * `build` - How to build the version from source, if it should be built even though it has an official build.
+/// * `platform` - The platform to stage the version for.
+/// * `release` - The upstream release of the version, as fetched by `fetch_upstream_release`.
+/// * `client` - A reference to the HTTP client.
+/// * `config` - A reference to the configuration file.
+/// * `staging` - A reference to the staging directory.
+///
+/// # Returns
+///
+/// * `Result<Option<InstallSource>>` - Returns `InstallSource::Build` if the version was built from source, or `None` if it was downloaded.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * There is no official build that runs on this system and `Config::source_build_fallback` isn't set.
+/// * There is a problem downloading the version.
+/// * There is a problem handling building from source.
+/// * There is a problem unarchiving the downloaded file.
+///
+/// # Example
+///
+/// ```rust
+/// let staging = filesystem::prepare_staging(root, &version.tag_name).await?;
+/// let release = fetch_upstream_release(&client, &version).await?;
+/// let source = stage_version(&version, None, &Platform::current(), &release, &client, &config, &staging).await?;
+/// ```
+async fn stage_version(
+    version: &ParsedVersion,
+    build: Option<&BuildOptions>,
+    platform: &Platform,
+    release: &UpstreamRelease,
+    client: &Client,
+    config: &ConfigFile,
+    staging: &Path,
+) -> Result<Option<InstallSource>> {
+    let staged_version = staging.join(&version.tag_name);
+
+    let downloaded_archive = match version.version_type {
+        VersionType::Normal | VersionType::Latest | VersionType::Nightly => {
+            let build_release = build.is_some()
+                || (version.version_type == VersionType::Nightly
+                    && config.config.enable_release_build == Some(true));
+
+            if !platform.is_current() {
+                download_version(client, version, platform, release, staging, &config.config).await
+            } else if build_release {
+                handle_building_from_source(version, build, &config.config, &staged_version).await
+            } else {
+                // The official Linux builds need a recent glibc, check before downloading one that can't start
+                match libc::check(
+                    &version.tag_name,
+                    version.semver.as_ref(),
+                    libc::detect().as_ref(),
+                ) {
+                    Ok(()) => {
+                        download_version(
+                            client,
+                            version,
+                            platform,
+                            release,
+                            staging,
+                            &config.config,
+                        )
+                        .await
+                    }
+                    Err(error) => Ok(PostDownloadVersionType::Unavailable(error.to_string())),
+                }
+            }
+        }
+        VersionType::Hash | VersionType::Git => {
+            handle_building_from_source(version, build, &config.config, &staged_version).await
+        }
+        VersionType::NightlyRollback | VersionType::Local => Ok(PostDownloadVersionType::None),
+    }?;
+
+    match downloaded_archive {
+        PostDownloadVersionType::Standard(downloaded_archive) => {
+            unarchive::start(&downloaded_archive).await?;
+        }
+        PostDownloadVersionType::Unavailable(reason) if !platform.is_current() => {
+            return Err(anyhow!(reason));
+        }
+        PostDownloadVersionType::Unavailable(reason) => {
+            if config.config.source_build_fallback != Some(true) {
+                return Err(anyhow!(
+                    "{reason}. Set `source_build_fallback` to true in the config to build it from source instead"
+                ));
+            }
+            warn!("{reason}, building it from source instead");
+            handle_building_from_source(version, build, &config.config, &staged_version).await?;
+            return Ok(Some(build_source(version, build, &config.config).await?));
+        }
+        PostDownloadVersionType::Hash => {
+            return Ok(Some(build_source(version, build, &config.config).await?));
+        }
+        PostDownloadVersionType::None | PostDownloadVersionType::Expanded => (),
+    }
+
+    Ok(None)
+}
+
+/// Returns the repository and ref a version is built from.
+///
+/// Git sources name their own repository, commits and releases are fetched from `Config::git_remote` by their full hash or tag.
+fn git_source(version: &ParsedVersion, config: &Config) -> Result<GitSource> {
+    if version.version_type == VersionType::Git {
+        return helpers::version::parse_git_source(&version.non_parsed_string, config)
+            .unwrap_or_else(|| Err(anyhow!("{} is not a git source", version.non_parsed_string)));
+    }
+
+    let git_ref = match version.version_type {
+        VersionType::Hash => &version.non_parsed_string,
+        _ => &version.tag_name,
+    };
+    Ok(GitSource {
+        remote: helpers::version::git_remote(config).to_string(),
+        git_ref: git_ref.clone(),
+        name: version.tag_name.clone(),
+    })
+}
+
+/// Returns the install source recorded for a version built from source, with the patches that were applied to it.
+async fn build_source(
+    version: &ParsedVersion,
+    build: Option<&BuildOptions>,
+    config: &Config,
+) -> Result<InstallSource> {
+    let source = git_source(version, config)?;
+
+    let mut patches = Vec::new();
+    for patch in build
+        .map(|build| build.patches.as_slice())
+        .unwrap_or_default()
+    {
+        patches.push(AppliedPatch {
+            path: patch.display().to_string(),
+            sha256: checksum::hash_file(patch).await?,
+        });
+    }
+
+    Ok(InstallSource::Build {
+        remote: Some(source.remote),
+        git_ref: source.git_ref,
+        patches,
+        profile: build.and_then(|build| build.profile.clone()),
+    })
+}
+
+/// Checks whether an installed version was built with other patches or another build profile than `build` uses, so it has to be built again.
+///
+/// # Arguments
+///
+/// * `installed` - A reference to the directory of the installed version.
+/// * `build` - How the version is about to be built, `None` if it is downloaded.
+///
+/// # Returns
+///
+/// * `Result<bool>` - Returns `true` if the patches, compared by their SHA-256, or the profile differ from the ones recorded in the install metadata.
+async fn build_changed(installed: &Path, build: Option<&BuildOptions>) -> Result<bool> {
+    let Some(build) = build else {
+        return Ok(false);
+    };
+
+    let mut requested = Vec::new();
+    for patch in &build.patches {
+        requested.push(checksum::hash_file(patch).await?);
+    }
+
+    let (recorded, profile) = match read_metadata(installed)
+        .await?
+        .and_then(|metadata| metadata.source)
+    {
+        Some(InstallSource::Build {
+            patches, profile, ..
+        }) => (
+            patches.into_iter().map(|patch| patch.sha256).collect(),
+            profile,
+        ),
+        _ => (Vec::new(), None),
+    };
+
+    Ok(requested != recorded || build.profile != profile)
+}
+
+/// Returns how a version is built from source, or `None` if it should be downloaded.
+///
+/// The patches configured for the version in `Config::patches`, under the version as given or its tag name, come first, followed by the patches in `options`.
+/// Each patch path is resolved to an absolute path, so the patches can be applied from inside `neovim-git`.
+///
+/// # Arguments
+///
+/// * `version` - A reference to the parsed version to build.
+/// * `options` - The options given for the build, `None` unless the version should be built from source.
+/// * `config` - A reference to the configuration to read the patches from.
+///
+/// # Returns
+///
+/// * `Result<Option<BuildOptions>>` - Returns the options to build with, or `None` if no options were given and no patches are configured.
+///
+/// # Errors
+///
+/// This function will return an error if a patch file doesn't exist or the build profile isn't defined.
+async fn resolve_build_options(
+    version: &ParsedVersion,
+    options: Option<&BuildOptions>,
+    config: &Config,
+) -> Result<Option<BuildOptions>> {
+    let configured = config
+        .patches
+        .as_ref()
+        .and_then(|patches| {
+            patches
+                .get(&version.non_parsed_string)
+                .or_else(|| patches.get(&version.tag_name))
+        })
+        .into_iter()
+        .flatten()
+        .map(PathBuf::from);
+
+    let given = options.map(|options| options.patches.iter().cloned());
+    let mut patches = Vec::new();
+    for patch in configured.chain(given.into_iter().flatten()) {
+        patches.push(
+            fs::canonicalize(&patch)
+                .await
+                .map_err(|error| anyhow!("Failed to read patch {}: {error}", patch.display()))?,
+        );
+    }
+
+    let profile = options.and_then(|options| options.profile.clone());
+    if let Some(profile) = &profile {
+        build_profile(config, profile)?;
+    }
+
+    if options.is_none() && patches.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(BuildOptions { patches, profile }))
+}
+
+/// Returns the build profile called `name` from `Config::build_profiles`.
+///
+/// # Errors
+///
+/// This function will return an error listing the defined profiles if there is no profile called `name`.
+fn build_profile<'a>(config: &'a Config, name: &str) -> Result<&'a BuildProfile> {
+    let profiles = config.build_profiles.as_ref();
+    profiles
+        .and_then(|profiles| profiles.get(name))
+        .ok_or_else(|| {
+            let defined: Vec<&str> = profiles
+                .into_iter()
+                .flat_map(|profiles| profiles.keys())
+                .map(String::as_str)
+                .collect();
+            match defined.as_slice() {
+                [] => anyhow!("There is no build profile named {name}, define it in `build_profiles` in the config"),
+                defined => anyhow!(
+                    "There is no build profile named {name}, the config defines: {}",
+                    defined.join(", ")
+                ),
+            }
+        })
+}
+
+/// Asynchronously prepares the rollback for the nightly version(s) of Neovim.
+///
+/// This function checks if the nightly version is used and if the rollback limit is not zero.
+/// If these conditions are met, it returns the directory the currently installed nightly should be moved to once the new nightly is in place.
+/// A leftover rollback directory with the same ID is removed first.
+///
+/// # Arguments
+///
+/// * `config` - A reference to the configuration object.
+/// * `root` - A reference to the downloads directory.
+///
+/// # Returns
+///
+/// * `Result<Option<PathBuf>>` - Returns a `Result` that contains the rollback directory, or `None` if no rollback should be created.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * There is a failure in reading the nightly JSON file.
+/// * There is a failure in parsing the JSON file.
+/// * There is a failure in removing a leftover rollback directory.
+///
+/// # Example
+///
+/// ```rust
+/// let config = Config::default();
+/// let rollback = prepare_rollback(&config, root).await?;
+/// ```
+async fn prepare_rollback(config: &Config, root: &Path) -> Result<Option<PathBuf>> {
+    if !helpers::version::is_version_used("nightly", config).await {
+        return Ok(None);
+    }
+
+    if config.rollback_limit.unwrap_or(3) == 0 {
+        return Ok(None);
+    }
+
+    let nightly_file = fs::read_to_string(root.join("nightly").join("bob.json")).await?;
+    let json_struct: UpstreamVersion = serde_json::from_str(&nightly_file)?;
+    let id: String = json_struct
+        .target_commitish
+        .as_ref()
+        .unwrap()
+        .chars()
+        .take(7)
+        .collect();
+
+    let rollback = root.join(format!("nightly-{id}"));
+    if fs::metadata(&rollback).await.is_ok() {
+        fs::remove_dir_all(&rollback).await?;
+    }
+
+    info!("Creating rollback: nightly-{id}");
+    Ok(Some(rollback))
+}
+
+/// Asynchronously finishes a nightly rollback after the previous nightly was moved into it.
+///
+/// This function updates the JSON file in the rollback directory with the rollback's name and removes the oldest rollbacks if there are more than the rollback limit.
+///
+/// # Arguments
+///
+/// * `config` - A reference to the configuration object.
+/// * `rollback` - A reference to the rollback directory.
+///
+/// # Returns
+///
+/// * `Result<()>` - Returns a `Result` that contains `()` on success, or an error on failure.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * There is a failure in reading or parsing the JSON file.
+/// * There is a failure in writing the updated JSON file.
+/// * There is a failure in producing the vector of nightly versions.
+/// * There is a failure in removing the oldest version.
+///
+/// # Example
+///
+/// ```rust
+/// let config = Config::default();
+/// finish_rollback(&config, Path::new("/path/to/bob/nightly-abcdefg")).await?;
+/// ```
+async fn finish_rollback(config: &Config, rollback: &Path) -> Result<()> {
+    let json_path = rollback.join("bob.json");
+    let nightly_file = fs::read_to_string(&json_path).await?;
+    let mut json_struct: UpstreamVersion = serde_json::from_str(&nightly_file)?;
+    if let Some(name) = rollback.file_name() {
+        json_struct.tag_name = name.to_string_lossy().to_string();
+    }
+
+    let json_file = serde_json::to_string(&json_struct)?;
+    fs::write(json_path, json_file).await?;
+
+    let rollback_limit: usize = config.rollback_limit.unwrap_or(3).into();
+    let mut nightly_vec = produce_nightly_vec(config).await?;
+    while nightly_vec.len() > rollback_limit {
+        let oldest_path = nightly_vec.pop().unwrap().path;
+        fs::remove_dir_all(oldest_path).await?;
+    }
+
+    Ok(())
+}
+
+/// Asynchronously prints the commits between two versions of Neovim.
+///
+/// This function fetches the commits between the published dates of the local and upstream versions of Neovim.
+/// It then prints each commit with the author's name in blue and the commit message.
+///
+/// # Arguments
+///
+/// * `client` - A reference to the HTTP client.
+/// * `local` - A reference to the local version of Neovim.
+/// * `upstream` - A reference to the upstream version of Neovim.
+///
+/// # Returns
+///
+/// * `Result<()>` - Returns a `Result` that contains `()` on success, or an error on failure.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * There is a failure in fetching the commits between the published dates of the local and upstream versions.
+///
+/// # Example
+///
+/// ```rust
+/// let client = Client::new();
+/// let local = UpstreamVersion::get_local_version();
+/// let upstream = UpstreamVersion::get_upstream_version(&client).await?;
+/// print_commits(&client, &local, &upstream).await?;
+/// ```
+async fn print_commits(
+    client: &Client,
+    local: &UpstreamVersion,
+    upstream: &UpstreamVersion,
+) -> Result<()> {
+    let commits =
+        get_commits_for_nightly(client, &local.published_at, &upstream.published_at).await?;
+
+    for commit in commits {
+        println!(
+            "| {} {}\n",
+            Paint::blue(commit.commit.author.name).bold(),
+            commit.commit.message.replace('\n', "\n| ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Asynchronously downloads a specified version of Neovim.
+///
+/// This function sends a request to download the specified version of Neovim based on the version type.
+/// If the version type is Normal, Nightly, or Latest, it looks up the archive for the platform in the release assets and downloads it.
+/// If the archive cache is enabled, a cached archive of a release is used instead of downloading it, and downloaded releases are added to the cache.
+/// The archive is hashed while it streams in and checked against the release checksum before it is considered complete.
+/// Tarballs are also expanded into `root` while they stream in, and the archive is only written to disk if it is added to the cache
+/// or if the server allows resuming the download. Such downloads are kept in `root/partial` when interrupted and resumed by the next attempt.
+/// If the version type is Hash, it handles building from the source.
+/// If the version type is `NightlyRollback`, it does nothing.
+///
+/// # Arguments
+///
+/// * `client` - A reference to the HTTP client.
+/// * `version` - A reference to the parsed version of Neovim to be downloaded.
+/// * `platform` - The platform to download Neovim for.
+/// * `upstream` - The upstream release of the version, its assets are used to pick the archive.
+/// * `root` - A reference to the path where the downloaded file will be saved.
+/// * `config` - A reference to the configuration object.
+///
+/// # Returns
+///
+/// * `Result<PostDownloadVersionType>` - Returns a `Result` that contains a `PostDownloadVersionType` on success, or an error on failure.
+///   `PostDownloadVersionType::Unavailable` is returned when the release exists but has no archive for the platform, or nightly currently has no release.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * The release doesn't exist.
+/// * There is a failure in fetching the checksum of the version.
+/// * There is a failure in sending the request to download the version.
+/// * The response status is not 200.
+/// * The checksum of the downloaded archive does not match.
+/// * There is a failure in creating the file where the downloaded version will be saved.
+/// * There is a failure in writing the downloaded bytes to the file.
+///
+/// # Example
+///
+/// ```rust
+/// let client = Client::new();
+/// let version = ParsedVersion::parse("0.5.0");
+/// let root = Path::new("/path/to/save");
+/// let config = Config::default();
+/// let upstream = fetch_upstream_release(&client, &version).await?;
+/// let result = download_version(&client, &version, &Platform::current(), &upstream, &root, &config).await;
+/// ```
+async fn download_version(
+    client: &Client,
+    version: &ParsedVersion,
+    platform: &Platform,
+    upstream: &UpstreamRelease,
+    root: &Path,
+    config: &Config,
+) -> Result<PostDownloadVersionType> {
+    match version.version_type {
+        VersionType::Normal | VersionType::Nightly | VersionType::Latest => {
+            let local_version = |file_format: String| LocalVersion {
+                file_name: version.tag_name.clone(),
+                file_format,
+                path: root.display().to_string(),
+                semver: version.semver.clone(),
+            };
+
+            // Nightly archives change under the same tag, so only releases are cached
+            let platform_key = platform.to_string();
+            let use_cache =
+                cache::is_enabled(config) && version.version_type != VersionType::Nightly;
+            if use_cache {
+                if let Some(entry) =
+                    cache::restore(config, &version.tag_name, &platform_key, root).await?
+                {
+                    return Ok(PostDownloadVersionType::Standard(local_version(
+                        entry.file_format,
+                    )));
+                }
+            }
+
+            ensure_online(&format!("Downloading {}", version.tag_name))?;
+
+            let release = match upstream {
+                UpstreamRelease::Found(upstream) => {
+                    match select_release_archive(&upstream.assets, platform, &version.tag_name) {
+                        Ok(release) => release,
+                        Err(error) => {
+                            return Ok(PostDownloadVersionType::Unavailable(error.to_string()));
+                        }
+                    }
+                }
+                // Nightly is republished by every upstream build, so it is missing while a build failed
+                UpstreamRelease::Missing if version.version_type == VersionType::Nightly => {
+                    return Ok(PostDownloadVersionType::Unavailable(
+                        "There is no nightly release right now, the latest upstream nightly build may have failed".to_string(),
+                    ));
+                }
+                UpstreamRelease::Missing => {
+                    return Err(anyhow!(
+                        "Version does not exist in Neovim releases. Please check available versions with 'bob list-remote'"
+                    ));
+                }
+                UpstreamRelease::Unknown => {
+                    guess_release_archive(&version.tag_name, version.semver.as_ref(), platform)
+                }
+            };
+            let format = release.format;
+            let file_name = format!("{}.{format}", version.tag_name);
+            let checksum = fetch_checksum(client, config, &release).await?;
+            let url = mirror_url(config, &release.archive.browser_download_url);
+
+            let response =
+                download::request(client, &url, &root.join("partial").join(&file_name)).await;
+
+            // Handle error case first so we don't need a match statement
+            let response = if let Err(error) = response {
+                return Err(anyhow!(
+                    "Failed to download version {}: {error}",
+                    version.tag_name
+                ));
+            } else {
+                response?
+            };
+
+            match response {
+                DownloadResponse::Ready(download) => {
+                    let pbw = PbWrapper::new(
+                        download.total_size(),
+                        format!("Downloading version {}", version.tag_name),
+                    );
+                    let archive = root.join(&file_name);
+
+                    // Tarballs are expanded while they download, the archive is only kept if it goes into the cache
+                    let sha256 = if format.is_streamable() {
+                        download
+                            .extract(
+                                format,
+                                &root.join(&version.tag_name),
+                                use_cache.then_some(archive.as_path()),
+                                checksum.as_deref(),
+                                |position| pbw.set_position(position),
+                            )
+                            .await?
+                    } else {
+                        download
+                            .save(&archive, checksum.as_deref(), |position| {
+                                pbw.set_position(position);
+                            })
+                            .await?
+                    };
+
+                    pbw.finish(format!(
+                        "Downloaded version {} to {}",
+                        version.tag_name,
+                        root.display()
+                    ));
+                    if checksum.is_some() {
+                        info!("Checksum matched!");
+                    }
+
+                    if use_cache {
+                        let entry = CacheEntry {
+                            tag_name: version.tag_name.clone(),
+                            platform: platform_key,
+                            sha256,
+                            file_format: format.to_string(),
+                            size: fs::metadata(&archive).await?.len(),
+                            cached_at: Utc::now(),
+                        };
+                        if let Err(error) = cache::store(config, &archive, entry).await {
+                            warn!(
+                                "Failed to add {} to the archive cache: {error}",
+                                version.tag_name
+                            );
+                        }
+                    }
+
+                    if !format.is_streamable() {
+                        return Ok(PostDownloadVersionType::Standard(local_version(
+                            format.to_string(),
+                        )));
+                    }
+                    if use_cache {
+                        fs::remove_file(&archive).await?;
+                    }
+                    Ok(PostDownloadVersionType::Expanded)
+                }
+                DownloadResponse::Failed(response) => {
+                    let is_not_found = response.status() == reqwest::StatusCode::NOT_FOUND;
+                    let error_text = response.text().await?;
+                    if is_not_found || error_text.contains("Not Found") {
+                        return Ok(PostDownloadVersionType::Unavailable(format!(
+                            "{} was not found, either {} doesn't exist or it has no build for {platform}",
+                            release.archive.name, version.tag_name
+                        )));
+                    }
+                    Err(anyhow!(
+                        "Failed to download version {}: {}",
+                        version.tag_name,
+                        error_text
+                    ))
+                }
+            }
+        }
+        VersionType::Hash | VersionType::Git => {
+            handle_building_from_source(version, None, config, &root.join(&version.tag_name)).await
+        }
+        VersionType::NightlyRollback | VersionType::Local => Ok(PostDownloadVersionType::None),
+    }
+}
+
+/// Asynchronously fetches the expected SHA-256 of the archive of a version.
+///
+/// The checksum manifest is small, so it is read into memory before the archive download starts.
+/// This lets `download_version` verify the archive while it is being streamed to disk.
+///
+/// # Arguments
+///
+/// * `client` - A reference to the HTTP client.
+/// * `config` - A reference to the configuration object.
+/// * `release` - A reference to the archive that is about to be downloaded and its checksum file.
+///
+/// # Returns
+///
+/// * `Result<Option<String>>` - Returns the hex encoded checksum, or `None` if the release doesn't provide one.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * There is a failure in sending the request.
+/// * The manifest doesn't list the archive.
+///
+/// # Example
+///
+/// ```rust
+/// let checksum = fetch_checksum(&client, &config, &release).await?;
+/// ```
+async fn fetch_checksum(
+    client: &Client,
+    config: &Config,
+    release: &ReleaseArchive,
+) -> Result<Option<String>> {
+    let Some(checksum) = &release.checksum else {
+        warn!("No checksum provided, skipping checksum verification");
+        return Ok(None);
+    };
+
+    let response = client
+        .get(mirror_url(config, &checksum.browser_download_url))
+        .header("user-agent", "bob")
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        warn!("No checksum provided, skipping checksum verification");
+        return Ok(None);
+    }
+
+    let manifest = response.text().await?;
+    let checksum = find_checksum(&manifest, &release.archive.name)?;
+
+    Ok(Some(checksum))
+}
+
+/// Asynchronously handles the building of a specified version from source.
+///
+/// This function checks for the presence of necessary tools (like Clang, GCC, Cmake, and Git) in the system.
+///
+/// It then:
 /// 1. Proceeds to create a directory named "neovim-git" if it doesn't exist; and sets the current directory to it.
-/// 2. Initializes a Git repository if one doesn't exist; and sets the remote to Neovim's GitHub repository.
-/// 3. Fetches the specified version from the remote repository and checks out the fetched files.
-/// 4. Builds the fetched files and installs them to a specified location.
+/// 2. Initializes a Git repository if one doesn't exist.
+/// 3. Builds the version in a free worktree of the repository, see `build_in_worktree`.
+///    Other bob processes may use the downloads directory while the build runs.
+/// 4. Moves the build to `install_dir` once it holds the lock again, so `install_dir` is never written to while the lock is released.
+///
+/// # Arguments
+///
+/// * `version` - A reference to the parsed version of Neovim to be built.
+/// * `build` - A reference to the options to build with, if any.
+/// * `config` - A reference to the configuration object.
+/// * `install_dir` - A reference to the directory the build is installed to.
+///
+/// # Returns
+///
+/// * `Result<PostDownloadVersionType>` - Returns a `Result` that contains a `PostDownloadVersionType` on success, or an error on failure.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// * The necessary tools are not installed in the system.
+/// * There is a failure in creating the "neovim-git" directory.
+/// * There is a failure in initializing the Git repository.
+/// * There is a failure in fetching, patching or building the version, see `build_in_worktree`.
+/// * There is a failure in moving the build to `install_dir`.
+///
+/// # Example
+///
+/// ```rust
+/// let version = ParsedVersion::parse("0.5.0");
+/// let config = Config::default();
+/// let result = handle_building_from_source(&version, None, &config, Path::new("/path/to/install")).await;
+/// ```
+#[rustfmt::skip]
+async fn handle_building_from_source(version: &ParsedVersion, build: Option<&BuildOptions>, config: &Config, install_dir: &Path) -> Result<PostDownloadVersionType> {
+    check_build_tools().await?;
+
+    if let Err(error) = Command::new("git").output().await {
+        if error.kind() == std::io::ErrorKind::NotFound {
+            return Err(anyhow!(
+                "Git has to be installed in order to build neovim from source"
+            ));
+        }
+    }
+
+    // create neovim-git if it does not exist
+    let repo = directories::get_downloads_directory(config).await?.join("neovim-git");
+    if let Err(error) = fs::metadata(&repo).await {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => {
+                fs::create_dir(&repo).await?;
+            }
+            _ => return Err(anyhow!("unknown error: {error}")),
+        }
+    }
+
+    env::set_current_dir(&repo)?; // cd into neovim-git
+
+    // check if repo is initialized
+    if let Err(error) = fs::metadata(".git").await {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => {
+                Command::new("git").arg("init").stdout(Stdio::null())
+                    .spawn()?.wait()
+                    .await?;
+            }
+
+            _ => return Err(anyhow!("unknown error: {error}")),
+        }
+    }
+
+    let source = git_source(version, config)?;
+    ensure_online("Fetching the neovim source")?;
+
+    // the build only touches its own worktree and installs to a prefix inside it, so it doesn't block other bob processes
+    let (prefix, worktree) = lock::unlocked(build_in_worktree(version, &source, build, config, &repo)).await??;
+
+    // the staging directory is only used once the lock is held again, another process may have cleaned it meanwhile
+    if let Some(parent) = install_dir.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    if fs::metadata(install_dir).await.is_ok() {
+        fs::remove_dir_all(install_dir).await?;
+    }
+    fs::rename(&prefix, install_dir).await?;
+    drop(worktree);
+
+    let mut file = File::create(install_dir.join("full-hash.txt")).await?;
+    file.write_all(source.git_ref.as_bytes()).await?;
+
+    Ok(PostDownloadVersionType::Hash)
+}
+
+/// The most worktrees `neovim-git` keeps, and so the most source builds that run at once.
+const MAX_WORKTREES: usize = 8;
+
+/// Name of the file marking an entry of the dependency cache whose dependencies are completely built.
+const DEPS_READY: &str = ".bob-ready";
+
+/// Name of the file in a build directory recording the settings its cmake cache was configured with.
+const CACHE_SETTINGS: &str = ".bob-settings.json";
+
+/// Name of the lock file in `neovim-git` that is held while a build fetches into the repository or adds a worktree to it.
+const REPOSITORY_LOCK: &str = "repository.lock";
+
+/// Where a build finds the bundled dependencies of Neovim.
+///
+/// # Variants
+///
+/// * `InTree` - Build them in `.deps` of the source tree, as make does by default.
+/// * `Build(PathBuf)` - Build them in this entry of the dependency cache.
+/// * `Cached(PathBuf)` - Use the ones an earlier build left in this entry of the dependency cache.
+#[derive(Debug, Clone, PartialEq)]
+enum BundledDeps {
+    InTree,
+    Build(PathBuf),
+    Cached(PathBuf),
+}
+
+impl BundledDeps {
+    /// Returns the directory the dependencies are built in, which installs them to its `usr` directory.
+    fn directory(&self) -> Result<PathBuf> {
+        match self {
+            BundledDeps::InTree => Ok(env::current_dir()?.join(".deps")),
+            BundledDeps::Build(entry) | BundledDeps::Cached(entry) => Ok(entry.clone()),
+        }
+    }
+}
+
+/// Asynchronously fetches a version into a free worktree of the `neovim-git` repository and builds it there.
+///
+/// Worktrees keep their `build` directory, so building a commit close to the one built there before only recompiles what changed.
+/// A worktree stays locked until the returned lock is dropped, so concurrent bob processes each build in a worktree of their own.
+/// Fetching into the shared repository and adding worktrees to it is serialized with `REPOSITORY_LOCK`.
+/// The bundled dependencies come from the dependency cache in `neovim-git/deps`, see `cached_deps`.
 ///
 /// # Arguments
 ///
 /// * `version` - A reference to the parsed version of Neovim to be built.
+/// * `source` - A reference to the repository and ref to fetch the version from.
+/// * `build` - A reference to the options to build with, if any.
 /// * `config` - A reference to the configuration object.
+/// * `repo` - A reference to the path of the `neovim-git` repository.
 ///
 /// # Returns
 ///
-/// * `Result<PostDownloadVersionType>` - Returns a `Result` that contains a `PostDownloadVersionType` on success, or an error on failure.
+/// * `Result<(PathBuf, FileLock)>` - Returns the directory the build was installed to, and the lock of the worktree it was built in.
 ///
 /// # Errors
 ///
 /// This function will return an error if:
-/// * The necessary tools are not installed in the system.
-/// * There is a failure in creating the "neovim-git" directory.
-/// * There is a failure in initializing the Git repository.
-/// * There is a failure in setting the remote repository.
+/// * All worktrees are in use by other bob processes.
 /// * There is a failure in fetching the specified version from the remote repository.
-/// * There is a failure in checking out the fetched files.
+/// * There is a failure in creating the worktree or checking out the fetched files.
+/// * A patch doesn't apply.
 /// * There is a failure in building and installing the fetched files.
 ///
 /// # Example
 ///
 /// ```rust
-/// let version = ParsedVersion::parse("0.5.0");
-/// let config = Config::default();
-/// let result = handle_building_from_source(&version, &config).await;
+/// let (prefix, worktree) = build_in_worktree(&version, &source, None, &config, Path::new("/path/to/bob/neovim-git")).await?;
 /// ```
-#[allow(clippy::too_many_lines)]
 #[rustfmt::skip]
-async fn handle_building_from_source(version: &ParsedVersion, config: &Config) -> Result<PostDownloadVersionType> {
+async fn build_in_worktree(version: &ParsedVersion, source: &GitSource, build: Option<&BuildOptions>, config: &Config, repo: &Path) -> Result<(PathBuf, FileLock)> {
+    let worktrees = repo.join("worktrees");
+    fs::create_dir_all(&worktrees).await?;
+    let (index, lock) = acquire_worktree(&worktrees).await?;
+    let worktree = worktrees.join(index.to_string());
+    let git_ref = &source.git_ref;
+    // FETCH_HEAD is shared by all worktrees, so each fetches into a ref of its own
+    let fetched = format!("refs/bob/worktree-{index}");
+
+    // git can't update the shallow file of the repository from two processes at once, so builds fetch one after another
+    let repository_lock = FileLock::acquire(&repo.join(REPOSITORY_LOCK), LockMode::Wait(Duration::MAX)).await?;
+    let fetch_successful = Command::new("git").current_dir(repo).arg("fetch").arg("--depth").arg("1").arg(&source.remote).arg(format!("+{git_ref}:{fetched}"))
+        .spawn()?.wait().await?.success();
+
+    if !fetch_successful {
+        if version.version_type == VersionType::Hash {
+            return Err(anyhow!("fetching remote failed, try providing the full commit hash"));
+        }
+        return Err(anyhow!("Failed to fetch {git_ref} from {}", source.remote));
+    }
+
+    if fs::metadata(worktree.join(".git")).await.is_ok() {
+        // checkout fetched files, discarding what earlier patches changed but keeping the ignored build directories
+        let checked_out = Command::new("git").current_dir(&worktree).arg("checkout").arg("--force").arg("--detach").arg(&fetched).stdout(Stdio::null())
+            .spawn()?.wait().await?.success();
+        if !checked_out {
+            return Err(anyhow!("Failed to check out {git_ref} in {}", worktree.display()));
+        }
+        Command::new("git").current_dir(&worktree).arg("clean").arg("--force").arg("-d").arg("--quiet")
+            .spawn()?.wait().await?;
+    } else {
+        if fs::metadata(&worktree).await.is_ok() {
+            filesystem::remove_dir(&worktree.to_string_lossy()).await?;
+        }
+        Command::new("git").current_dir(repo).arg("worktree").arg("prune")
+            .spawn()?.wait().await?;
+        let added = Command::new("git").current_dir(repo).arg("worktree").arg("add").arg("--force").arg("--detach").arg(&worktree).arg(&fetched).stdout(Stdio::null())
+            .spawn()?.wait().await?.success();
+        if !added {
+            return Err(anyhow!("Failed to create the worktree {}", worktree.display()));
+        }
+    }
+    drop(repository_lock);
+
+    env::set_current_dir(&worktree)?;
+
+    for patch in build.map(|build| build.patches.as_slice()).unwrap_or_default() {
+        info!("Applying {}", patch.display());
+        let applied = Command::new("git").arg("apply").arg(patch)
+            .spawn()?.wait().await?.success();
+
+        if !applied {
+            return Err(anyhow!("Failed to apply {} to {git_ref}", patch.display()));
+        }
+    }
+
+    let profile = build.and_then(|build| build.profile.as_deref());
+    let (deps, deps_lock) = cached_deps(repo, config, profile).await?;
+
+    // the install prefix stays the same for every build in this worktree, so make doesn't have to configure cmake from scratch
+    let prefix = worktrees.join(format!("{index}-install"));
+    if fs::metadata(&prefix).await.is_ok() {
+        fs::remove_dir_all(&prefix).await?;
+    }
+    build_neovim(config, &prefix, profile, &deps, None).await?;
+
+    if let BundledDeps::Build(entry) = &deps {
+        File::create(entry.join(DEPS_READY)).await?;
+    }
+    drop(deps_lock);
+
+    Ok((prefix, lock))
+}
+
+/// Asynchronously locks the first worktree in `worktrees` that no other bob process is building in.
+///
+/// # Arguments
+///
+/// * `worktrees` - A reference to the directory holding the worktrees of `neovim-git`.
+///
+/// # Returns
+///
+/// * `Result<(usize, FileLock)>` - Returns the number of the worktree and its lock.
+///
+/// # Errors
+///
+/// This function will return an error if all `MAX_WORKTREES` worktrees are locked.
+async fn acquire_worktree(worktrees: &Path) -> Result<(usize, FileLock)> {
+    for index in 0..MAX_WORKTREES {
+        let lock_file = worktrees.join(format!("{index}.lock"));
+        if let Ok(lock) = FileLock::acquire(&lock_file, LockMode::FailFast).await {
+            return Ok((index, lock));
+        }
+    }
+
+    Err(anyhow!(
+        "All {MAX_WORKTREES} build worktrees are in use by other bob processes"
+    ))
+}
+
+/// Asynchronously looks up the bundled dependencies of the source in the current directory in the dependency cache of `repo`.
+///
+/// Entries are keyed by the content hash of `cmake.deps`, or `third-party` before Neovim 0.9, together with the compiler and the build type.
+/// Commits that don't change the dependencies so share an entry and only the first of them builds it.
+/// An entry that still has to be built is locked, so concurrent builds needing it wait instead of building it twice.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the path of the `neovim-git` repository.
+/// * `config` - A reference to the configuration object.
+/// * `profile` - The name of the build profile the source is built with, if any.
+///
+/// # Returns
+///
+/// * `Result<(BundledDeps, Option<FileLock>)>` - Returns where the build finds its dependencies, and the lock to hold until it built them.
+///
+/// # Errors
+///
+/// This function will return an error if the build profile isn't defined, or the dependency definitions or the cache can't be read.
+///
+/// # Example
+///
+/// ```rust
+/// let (deps, deps_lock) = cached_deps(Path::new("/path/to/bob/neovim-git"), &config, None).await?;
+/// ```
+async fn cached_deps(
+    repo: &Path,
+    config: &Config,
+    profile: Option<&str>,
+) -> Result<(BundledDeps, Option<FileLock>)> {
+    let settings = profile
+        .map(|name| build_profile(config, name))
+        .transpose()?;
+    if settings.and_then(|profile| profile.bundled_deps) == Some(false) {
+        return Ok((BundledDeps::InTree, None));
+    }
+    let Some(definitions) = ["cmake.deps", "third-party"]
+        .into_iter()
+        .find(|definitions| Path::new(definitions).is_dir())
+    else {
+        return Ok((BundledDeps::InTree, None));
+    };
+
+    let compiler = settings
+        .and_then(|profile| profile.compiler.clone())
+        .or_else(|| env::var("CC").ok())
+        .unwrap_or_default();
+    let definitions = checksum::hash_directory(Path::new(definitions)).await?;
+    let key = Sha256::digest(format!(
+        "{definitions}\n{compiler}\n{}",
+        build_type(config, settings)
+    ));
+    let cache = repo.join("deps");
+    let entry = cache.join(&format!("{key:x}")[..16]);
+
+    if fs::metadata(entry.join(DEPS_READY)).await.is_ok() {
+        return Ok((BundledDeps::Cached(entry), None));
+    }
+
+    fs::create_dir_all(&cache).await?;
+    let lock =
+        FileLock::acquire(&entry.with_extension("lock"), LockMode::Wait(Duration::MAX)).await?;
+    // another build may have finished the entry while this one waited
+    if fs::metadata(entry.join(DEPS_READY)).await.is_ok() {
+        return Ok((BundledDeps::Cached(entry), None));
+    }
+    // cmake refuses to continue a build that another worktree started, so a failed one starts over
+    if fs::metadata(&entry).await.is_ok() {
+        filesystem::remove_dir(&entry.to_string_lossy()).await?;
+    }
+
+    Ok((BundledDeps::Build(entry), Some(lock)))
+}
+
+/// Checks that the compiler and build tools needed to build Neovim from source are installed.
+///
+/// # Returns
+///
+/// * `Result<()>` - Returns `Ok(())` if the tools are present.
+///
+/// # Errors
+///
+/// This function will return an error naming the missing tool if Clang or GCC, or CMake is not installed,
+/// or if it isn't run from a Visual Studio developer prompt on Windows.
+#[rustfmt::skip]
+async fn check_build_tools() -> Result<()> {
     cfg_if::cfg_if! {
         if #[cfg(windows)] {
             if env::var("VisualStudioVersion").is_err() {
@@ -547,189 +1950,290 @@ async fn handle_building_from_source(version: &ParsedVersion, config: &Config) -
         }
     }
 
-    if let Err(error) = Command::new("git").output().await {
-        if error.kind() == std::io::ErrorKind::NotFound {
-            return Err(anyhow!(
-                "Git has to be installed in order to build neovim from source"
-            ));
-        }
-    }
+    Ok(())
+}
 
-    // create neovim-git if it does not exist
-    let dirname = "neovim-git";
-    if let Err(error) = fs::metadata(dirname).await {
-        match error.kind() {
-            std::io::ErrorKind::NotFound => {
-                fs::create_dir(dirname).await?;
-            }
-            _ => return Err(anyhow!("unknown error: {error}")),
-        }
+/// Builds the Neovim source in the current directory and installs it to `install_dir`.
+///
+/// The source is built with make in its `build` directory, or with cmake in `build_dir` if it is set.
+/// cmake is configured again on every build, but the build directory is kept, so an earlier build in the same directory is continued incrementally.
+/// The bundled dependencies are built or reused as `deps` says, and cmake is pointed at them.
+/// The settings of a build profile are applied on top of the defaults, and the profile is recorded in `build-profile.json` next to the installed build.
+///
+/// # Arguments
+///
+/// * `config` - A reference to the configuration object.
+/// * `install_dir` - A reference to the directory the build is installed to.
+/// * `profile` - The name of the build profile from `Config::build_profiles` to build with, if any.
+/// * `deps` - A reference to where the build finds the bundled dependencies.
+/// * `build_dir` - The directory to build in instead of the `build` directory of the source tree, if any.
+///
+/// # Returns
+///
+/// * `Result<()>` - Returns `Ok(())` once the build is installed.
+///
+/// # Errors
+///
+/// This function will return an error if the build profile isn't defined, or the build or the installation fails.
+#[rustfmt::skip]
+async fn build_neovim(config: &Config, install_dir: &Path, profile: Option<&str>, deps: &BundledDeps, build_dir: Option<&Path>) -> Result<()> {
+    let profile = profile.map(|name| build_profile(config, name).map(|profile| (name, profile))).transpose()?;
+    let settings = profile.map(|(_, profile)| profile);
+
+    if build_dir.is_none() && fs::metadata("build/.ran-cmake").await.is_ok() {
+        // make only configures cmake, and so picks up the install prefix, when this marker is missing
+        fs::remove_file("build/.ran-cmake").await?;
     }
 
-    env::set_current_dir(dirname)?; // cd into neovim-git
+    let folder_name = install_dir;
 
-    // check if repo is initialized
-    if let Err(error) = fs::metadata(".git").await {
-        match error.kind() {
-            std::io::ErrorKind::NotFound => {
-                Command::new("git").arg("init").stdout(Stdio::null())
-                    .spawn()?.wait()
-                    .await?;
-            }
+    let build_type = build_type(config, settings);
 
-            _ => return Err(anyhow!("unknown error: {error}")),
-        }
-    }
+    discard_stale_cache(build_dir.unwrap_or(Path::new("build")), &cache_settings(config, settings)).await?;
 
-    {
+    let build_arg = format!("CMAKE_BUILD_TYPE={build_type}");
+    // the build directory remembers the dependencies of the last build, so they are always named
+    let deps_prefix = format!("-DDEPS_PREFIX={}", deps.directory()?.join("usr").display());
 
-    // check if repo has a remote
-    let remote = Command::new("git").arg("remote").arg("get-url").arg("origin").stdout(Stdio::null())
-        .spawn()?.wait().await?;
-    if remote.success() {
-        // set neovim's remote
-        Command::new("git").arg("remote").arg("set-url").arg("origin").arg("https://github.com/neovim/neovim.git")
-            .spawn()?.wait().await?;
-    } else {
-        // add neovim's remote otherwise
-        Command::new("git").arg("remote").arg("add").arg("origin").arg("https://github.com/neovim/neovim.git")
-            .spawn()?.wait().await?;
-    }
-    // fetch version from origin
-    let fetch_successful = Command::new("git").arg("fetch").arg("--depth").arg("1").arg("origin").arg(&version.non_parsed_string)
-        .spawn()?.wait().await?.success();
+    cfg_if::cfg_if! {
+        if #[cfg(windows)] {
 
-    if !fetch_successful {
-        return Err(anyhow!("fetching remote failed, try providing the full commit hash"));
-    }
+            let build_dir = build_dir.unwrap_or(Path::new("build"));
+            cmake_build(build_dir, build_arg.to_string(), build_type.to_string(), folder_name.to_string_lossy().to_string(), deps_prefix, settings, deps).await?;
 
-    // checkout fetched files
-    Command::new("git").arg("checkout").arg("FETCH_HEAD").stdout(Stdio::null())
-        .spawn()?
-        .wait()
-        .await?;
+        } else {
+            if let Some(build_dir) = build_dir {
+                cmake_build(build_dir, build_arg.to_string(), build_type.to_string(), folder_name.to_string_lossy().to_string(), deps_prefix, settings, deps).await?;
+                return record_build_profile(install_dir, profile).await;
+            }
 
+            let location_arg = format!("CMAKE_INSTALL_PREFIX={}", folder_name.to_string_lossy());
+            let mut make = Command::new("make");
+            make.arg(&location_arg).arg(&build_arg);
+            let mut flags = settings.map(BuildProfile::cmake_flags).unwrap_or_default();
+            flags.push(deps_prefix);
+            // make hands the flags to the shell as one string, so each is quoted
+            let flags: Vec<String> = flags.iter().map(|flag| shell_quote(flag)).collect();
+            make.arg(format!("CMAKE_EXTRA_FLAGS={}", flags.join(" ")));
+            match deps {
+                BundledDeps::InTree => (),
+                BundledDeps::Build(entry) => {
+                    make.arg(format!("DEPS_BUILD_DIR={}", entry.display()));
+                }
+                BundledDeps::Cached(_) => {
+                    make.arg("USE_BUNDLED=OFF");
+                }
+            }
+            if let Some(profile) = settings {
+                if profile.bundled_deps == Some(false) {
+                    make.arg("USE_BUNDLED=OFF");
+                }
+                if let Some(compiler) = &profile.compiler {
+                    make.env("CC", compiler);
+                }
+                if let Some(jobs) = profile.jobs {
+                    make.env("CMAKE_BUILD_PARALLEL_LEVEL", jobs.to_string());
+                }
+            }
+            handle_subprocess(&mut make).await?;
+            handle_subprocess(Command::new("make").arg("install")).await?;
+        }
     }
 
-    if fs::metadata("build").await.is_ok() {
-        filesystem::remove_dir("build").await?;
+    record_build_profile(install_dir, profile).await
+}
+
+/// Records the build profile a build was built with in `build-profile.json` next to the installed build.
+async fn record_build_profile(
+    install_dir: &Path,
+    profile: Option<(&str, &BuildProfile)>,
+) -> Result<()> {
+    if let Some((name, profile)) = profile {
+        let mut recorded = serde_json::to_value(profile)?;
+        recorded["name"] = serde_json::Value::from(name);
+        let mut file = File::create(install_dir.join("build-profile.json")).await?;
+        file.write_all(serde_json::to_string_pretty(&recorded)?.as_bytes())
+            .await?;
     }
-    fs::create_dir("build").await?;
 
-    let downloads_location = directories::get_downloads_directory(config).await?;
-    let folder_name = downloads_location.join(&version.tag_name[0..7]);
+    Ok(())
+}
 
-    let build_type = match config.enable_release_build {
-        Some(true) => "Release",
-        _ => "RelWithDebInfo",
-    };
+/// Returns the settings of a build that cmake keeps in its cache, to tell whether a build directory was configured for another build.
+///
+/// The number of jobs isn't part of them, since it doesn't change what is built.
+fn cache_settings(config: &Config, profile: Option<&BuildProfile>) -> String {
+    let compiler = profile
+        .and_then(|profile| profile.compiler.clone())
+        .or_else(|| env::var("CC").ok());
+    serde_json::json!({
+        "build_type": build_type(config, profile),
+        "compiler": compiler,
+        "cmake_flags": profile.map(BuildProfile::cmake_flags).unwrap_or_default(),
+        "bundled_deps": profile.and_then(|profile| profile.bundled_deps),
+    })
+    .to_string()
+}
 
-    let build_arg = format!("CMAKE_BUILD_TYPE={build_type}");
+/// Starts the cmake cache of `build_dir` over if it was configured with other settings, and records `settings` for the next build.
+///
+/// cmake keeps the compiler and every `-D` option in its cache, so an option a build doesn't pass anymore, such as `ENABLE_ASAN_UBSAN`
+/// of another profile, would keep its old value. Worktrees and checkouts keep their build directory, so building one with another profile
+/// or compiler removes `CMakeCache.txt` and `CMakeFiles`, which is a full rebuild, as it would be anyway with other flags.
+async fn discard_stale_cache(build_dir: &Path, settings: &str) -> Result<()> {
+    let recorded = build_dir.join(CACHE_SETTINGS);
+    if fs::read_to_string(&recorded).await.ok().as_deref() == Some(settings) {
+        return Ok(());
+    }
 
-    cfg_if::cfg_if! {
-        if #[cfg(windows)] {
+    if fs::metadata(build_dir.join("CMakeCache.txt")).await.is_ok() {
+        fs::remove_file(build_dir.join("CMakeCache.txt")).await?;
+    }
+    if fs::metadata(build_dir.join("CMakeFiles")).await.is_ok() {
+        fs::remove_dir_all(build_dir.join("CMakeFiles")).await?;
+    }
+    fs::create_dir_all(build_dir).await?;
+    fs::write(&recorded, settings).await?;
 
-            windows_deps(build_arg.to_string(), build_type.to_string(), folder_name.to_string_lossy().to_string()).await?;
+    Ok(())
+}
 
-        } else {
-            let location_arg = format!("CMAKE_INSTALL_PREFIX={}", folder_name.to_string_lossy());
-            handle_subprocess(Command::new("make").arg(&location_arg).arg(&build_arg)).await?;
-            handle_subprocess(Command::new("make").arg("install")).await?;
-        }
+/// Returns the cmake build type of a build, from its profile or `Config::enable_release_build`.
+fn build_type<'a>(config: &Config, profile: Option<&'a BuildProfile>) -> &'a str {
+    match (
+        profile.and_then(|profile| profile.build_type.as_deref()),
+        config.enable_release_build,
+    ) {
+        (Some(build_type), _) => build_type,
+        (None, Some(true)) => "Release",
+        (None, _) => "RelWithDebInfo",
     }
+}
 
-    let mut file = File::create(folder_name.join("full-hash.txt")).await?;
-    file.write_all(version.non_parsed_string.as_bytes()).await?;
-
-    Ok(PostDownloadVersionType::Hash)
+/// Quotes an argument for a POSIX shell.
+#[cfg(not(windows))]
+fn shell_quote(argument: &str) -> String {
+    format!("'{}'", argument.replace('\'', "'\\''"))
 }
 
-#[cfg(target_os = "windows")]
+/// Builds the Neovim source in the current directory with cmake in `build_dir`, and installs it to `folder_name`.
+///
+/// This is how Neovim is built on Windows, where there is no make, and how checkouts are built, since make always builds in the `build` directory of the source tree.
+/// The bundled dependencies are built first, unless the profile turns them off or `deps` has them already.
 #[rustfmt::skip]
-async fn windows_deps<S>(build_arg: S, build_type: S, folder_name: S) -> Result<()>
+async fn cmake_build<S>(build_dir: &Path, build_arg: S, build_type: S, folder_name: S, deps_prefix: S, profile: Option<&BuildProfile>, deps: &BundledDeps) -> Result<()>
 where
     S: AsRef<std::ffi::OsStr>
 {
-    if fs::metadata(".deps").await.is_ok() {
-        helpers::filesystem::remove_dir(".deps").await?;
+    let bundled_deps = profile.and_then(|profile| profile.bundled_deps) != Some(false);
+    let jobs = profile.and_then(|profile| profile.jobs).map(|jobs| jobs.to_string());
+    let compiler = profile.and_then(|profile| profile.compiler.as_ref());
+
+    if bundled_deps && !matches!(deps, BundledDeps::Cached(_)) {
+        let deps_dir = deps.directory()?;
+        if *deps == BundledDeps::InTree && fs::metadata(&deps_dir).await.is_ok() {
+            filesystem::remove_dir(&deps_dir.to_string_lossy()).await?;
+        }
+
+        // the dependencies were defined in third-party before Neovim 0.9
+        let definitions = if Path::new("cmake.deps").is_dir() { "cmake.deps" } else { "third-party" };
+        let mut configure = Command::new("cmake");
+        configure.arg("-S").arg(definitions).arg("-B").arg(&deps_dir).arg("-D").arg(&build_arg);
+        if let Some(compiler) = compiler {
+            configure.env("CC", compiler);
+        }
+        handle_subprocess(&mut configure).await?;
+        handle_subprocess(Command::new("cmake").arg("--build").arg(&deps_dir).arg("--config").arg(&build_type)).await?;
     }
 
-    handle_subprocess(Command::new("cmake").arg("-S").arg("cmake.deps").arg("-B").arg(".deps").arg("-D").arg(&build_arg)).await?;
-    handle_subprocess(Command::new("cmake").arg("--build").arg(".deps").arg("--config").arg(&build_type)).await?;
-    handle_subprocess(Command::new("cmake").arg("-B").arg("build").arg("-D").arg(&build_arg)).await?;
-    handle_subprocess(Command::new("cmake").arg("--build").arg("build").arg("--config").arg(build_type)).await?;
-    handle_subprocess(Command::new("cmake").arg("--install").arg("build").arg("--prefix").arg(&folder_name)).await?;
+    let mut configure = Command::new("cmake");
+    configure.arg("-B").arg(build_dir).arg("-D").arg(&build_arg).arg(&deps_prefix).args(profile.map(BuildProfile::cmake_flags).unwrap_or_default());
+    if !bundled_deps {
+        configure.arg("-DUSE_BUNDLED=OFF");
+    }
+    if let Some(compiler) = compiler {
+        configure.env("CC", compiler);
+    }
+    handle_subprocess(&mut configure).await?;
+
+    let mut build = Command::new("cmake");
+    build.arg("--build").arg(build_dir).arg("--config").arg(build_type);
+    if let Some(jobs) = &jobs {
+        build.env("CMAKE_BUILD_PARALLEL_LEVEL", jobs);
+    }
+    handle_subprocess(&mut build).await?;
+    handle_subprocess(Command::new("cmake").arg("--install").arg(build_dir).arg("--prefix").arg(&folder_name)).await?;
     Ok(())
 
 }
 
-/// Sends a GET request to the specified URL to download a specific version of Neovim.
-///
-/// # Arguments
-///
-/// * `client: &Client` - A reference to the `Client` used for making requests.
-/// * `config: &Config` - Contains the configuration settings.
-/// * `version: &ParsedVersion` - Contains the version information to be downloaded.
-/// * `get_sha256sum: bool` - A boolean indicating whether to get the sha256sum.
-///
-/// # Behavior
-///
-/// The function constructs the download URL based on the provided `version` and `config.github_mirror`. If `config.github_mirror` is `None`, it defaults to `<https://github.com>`.
-///
-/// It then sends a GET request to the constructed URL with the header "user-agent" set to "bob".
-///
-/// # Returns
-///
-/// * `Result<reqwest::Response, reqwest::Error>` - Returns a `Result` containing the server's response to the GET request. If the request fails, it returns an error.
+/// Rewrites a github.com download URL to use `Config::github_mirror`, if one is set.
 ///
 /// # Example
 ///
 /// ```rust
-/// let client = Client::new();
-/// let config = Config::default();
-/// let version = ParsedVersion { tag_name: "v0.2.2", semver: Version::parse("0.2.2").unwrap() };
-/// let response = send_request(&client, &config, &version, false).await?;
+/// let url = mirror_url(&config, "https://github.com/neovim/neovim/releases/download/v0.10.0/shasum.txt");
 /// ```
-///
-/// # Note
-///
-/// This function is asynchronous and must be awaited.
-///
-/// # See Also
-///
-/// * [`helpers::get_file_type`](src/helpers/file.rs)
-async fn send_request(
-    client: &Client,
-    config: &Config,
-    version: &ParsedVersion,
-    get_sha256sum: bool,
-) -> Result<reqwest::Response, reqwest::Error> {
-    let platform = helpers::get_platform_name(version.semver.as_ref());
-    let file_type = crate::FILETYPE_EXT;
-
-    let url = config.github_mirror.as_ref().map_or_else(
-        || "https://github.com".to_string(),
-        std::string::ToString::to_string,
-    );
-
-    let version_tag = &version.tag_name;
-    let request_url = if get_sha256sum {
-        if version.version_type == VersionType::Nightly
-            || version.semver.as_ref().unwrap() > &Version::new(0, 10, 4)
-        {
-            format!("{url}/neovim/neovim/releases/download/{version_tag}/shasum.txt")
-        } else {
-            format!(
-                "{url}/neovim/neovim/releases/download/{version_tag}/{platform}.{file_type}.sha256sum"
-            )
-        }
-    } else {
-        format!("{url}/neovim/neovim/releases/download/{version_tag}/{platform}.{file_type}")
-    };
+fn mirror_url(config: &Config, url: &str) -> String {
+    match (
+        &config.github_mirror,
+        url.strip_prefix("https://github.com"),
+    ) {
+        (Some(mirror), Some(path)) => format!("{}{path}", mirror.trim_end_matches('/')),
+        _ => url.to_string(),
+    }
+}
 
-    client
-        .get(request_url)
-        .header("user-agent", "bob")
-        .send()
-        .await
+#[cfg(test)]
+mod install_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn switching_profiles_discards_the_cmake_cache() {
+        let temp = tempfile::tempdir().unwrap();
+        let build_dir = temp.path().join("build");
+        let config = Config::default();
+        let asan = BuildProfile {
+            cmake_flags: Some(vec!["-DENABLE_ASAN_UBSAN=ON".to_string()]),
+            ..BuildProfile::default()
+        };
+        let more_jobs = BuildProfile {
+            jobs: Some(16),
+            ..BuildProfile::default()
+        };
+        let configure = || async {
+            fs::create_dir_all(build_dir.join("CMakeFiles"))
+                .await
+                .unwrap();
+            fs::write(build_dir.join("CMakeCache.txt"), "")
+                .await
+                .unwrap();
+        };
+
+        // A build directory of an earlier bob recorded no settings, so it is started over once
+        configure().await;
+        discard_stale_cache(&build_dir, &cache_settings(&config, Some(&asan)))
+            .await
+            .unwrap();
+        assert!(!build_dir.join("CMakeCache.txt").exists());
+
+        configure().await;
+        discard_stale_cache(&build_dir, &cache_settings(&config, Some(&asan)))
+            .await
+            .unwrap();
+        assert!(build_dir.join("CMakeCache.txt").exists());
+
+        discard_stale_cache(&build_dir, &cache_settings(&config, None))
+            .await
+            .unwrap();
+        assert!(!build_dir.join("CMakeCache.txt").exists());
+        assert!(!build_dir.join("CMakeFiles").exists());
+
+        // The number of jobs doesn't end up in the cache
+        configure().await;
+        discard_stale_cache(&build_dir, &cache_settings(&config, Some(&more_jobs)))
+            .await
+            .unwrap();
+        assert!(build_dir.join("CMakeCache.txt").exists());
+    }
 }
@@ -1,11 +1,17 @@
 use anyhow::Result;
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 use tracing::info;
 use yansi::Paint;
 
 use crate::{
     config::Config,
-    helpers::{self, directories, version::nightly::produce_nightly_vec},
+    helpers::{
+        self, directories,
+        version::{metadata::read_metadata, nightly::produce_nightly_vec, types::InstallMetadata},
+    },
 };
 
 /// Starts the list handler.
@@ -40,45 +46,77 @@ pub async fn start(config: Config) -> Result<()> {
         return Ok(());
     }
 
+    let mut names = Vec::new();
+    let mut sources = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            continue;
+        }
+
+        let path_name = path.file_name().unwrap().to_str().unwrap().to_string();
+
+        if is_version(&path_name) || is_custom_install(&path).await {
+            names.push(path_name);
+            sources.push(install_source(&path).await);
+        }
+    }
+
     let version_max_len = if has_rollbacks(&config).await? { 16 } else { 7 };
+    let version_max_len = names
+        .iter()
+        .map(String::len)
+        .fold(version_max_len, usize::max);
     let status_max_len = 9;
+    // The source column is only shown when a version didn't come from the Neovim releases
+    let source_max_len = sources
+        .iter()
+        .flatten()
+        .map(|source| source.chars().count())
+        .max()
+        .map(|len| len.max(6));
     let padding = 2;
 
+    let border = |left: &str, middle: &str, right: &str| {
+        let mut line = format!(
+            "{left}{}{middle}{}",
+            "─".repeat(version_max_len + (padding * 2)),
+            "─".repeat(status_max_len + (padding * 2))
+        );
+        if let Some(source_max_len) = source_max_len {
+            line.push_str(middle);
+            line.push_str(&"─".repeat(source_max_len + (padding * 2)));
+        }
+        line.push_str(right);
+        line
+    };
+    let source_cell = |source: &str| match source_max_len {
+        Some(source_max_len) => format!(
+            "{}{source}{}│",
+            " ".repeat(padding),
+            " ".repeat(padding + (source_max_len - source.chars().count()))
+        ),
+        None => String::new(),
+    };
+
+    println!("{}", border("┌", "┬", "┐"));
     println!(
-        "┌{}┬{}┐",
-        "─".repeat(version_max_len + (padding * 2)),
-        "─".repeat(status_max_len + (padding * 2))
-    );
-    println!(
-        "│{}Version{}│{}Status{}│",
+        "│{}Version{}│{}Status{}│{}",
         " ".repeat(padding),
         " ".repeat(padding + (version_max_len - 7)),
         " ".repeat(padding),
-        " ".repeat(padding + (status_max_len - 6))
-    );
-    println!(
-        "├{}┼{}┤",
-        "─".repeat(version_max_len + (padding * 2)),
-        "─".repeat(status_max_len + (padding * 2))
+        " ".repeat(padding + (status_max_len - 6)),
+        source_cell("Source")
     );
+    println!("{}", border("├", "┼", "┤"));
 
-    for path in paths {
-        if !path.is_dir() {
-            continue;
-        }
-
-        let path_name = path.file_name().unwrap().to_str().unwrap();
-
-        if !is_version(path_name) {
-            continue;
-        }
-
+    for (path_name, source) in names.iter().zip(&sources) {
         let version_pr = (version_max_len - path_name.len()) + padding;
         let status_pr = padding + status_max_len;
+        let source = source_cell(source.as_deref().unwrap_or_default());
 
         if helpers::version::is_version_used(path_name, &config).await {
             println!(
-                "│{}{path_name}{}│{}{}{}│",
+                "│{}{path_name}{}│{}{}{}│{source}",
                 " ".repeat(padding),
                 " ".repeat(version_pr),
                 " ".repeat(padding),
@@ -87,7 +125,7 @@ pub async fn start(config: Config) -> Result<()> {
             );
         } else {
             println!(
-                "│{}{path_name}{}│{}{}{}│",
+                "│{}{path_name}{}│{}{}{}│{source}",
                 " ".repeat(padding),
                 " ".repeat(version_pr),
                 " ".repeat(padding),
@@ -97,11 +135,7 @@ pub async fn start(config: Config) -> Result<()> {
         }
     }
 
-    println!(
-        "└{}┴{}┘",
-        "─".repeat(version_max_len + (padding * 2)),
-        "─".repeat(status_max_len + (padding * 2))
-    );
+    println!("{}", border("└", "┴", "┘"));
 
     Ok(())
 }
@@ -131,6 +165,43 @@ async fn has_rollbacks(config: &Config) -> Result<bool> {
     Ok(!list.is_empty())
 }
 
+/// Checks if a directory holds a version installed under a custom name, from an archive or a git source.
+///
+/// # Arguments
+///
+/// * `path` - A reference to the path of the directory.
+///
+/// # Returns
+///
+/// * `bool` - Returns `true` if the install metadata of the directory records a source, `false` otherwise.
+async fn is_custom_install(path: &Path) -> bool {
+    matches!(
+        read_metadata(path).await,
+        Ok(Some(InstallMetadata {
+            source: Some(_),
+            ..
+        }))
+    )
+}
+
+/// Returns where the version in a directory came from, when it wasn't downloaded from the Neovim releases.
+///
+/// # Arguments
+///
+/// * `path` - A reference to the path of the directory.
+///
+/// # Returns
+///
+/// * `Option<String>` - Returns the source recorded in the install metadata, for example `refs/pull/12345/head from https://github.com/neovim/neovim.git`, or `None`.
+async fn install_source(path: &Path) -> Option<String> {
+    read_metadata(path)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|metadata| metadata.source)
+        .map(|source| source.to_string())
+}
+
 /// Checks if a given string is a valid version.
 ///
 /// This function checks if the given string is "stable", contains "nightly", or matches the version or hash regex.
@@ -158,7 +229,7 @@ fn is_version(name: &str) -> bool {
             if crate::VERSION_REGEX.is_match(name) {
                 return true;
             }
-            crate::HASH_REGEX.is_match(name)
+            helpers::version::is_hash(name)
         }
     }
 }
@@ -174,6 +245,7 @@ mod list_handler_is_version_tests {
             ("stable", true),
             ("nightly-2023-10-01", true),
             ("invalid-version", false),
+            ("pr-12345", false),
             ("", false),
         ];
 
@@ -1,3 +1,5 @@
+pub mod alias_handler;
+pub mod cache_handler;
 pub mod erase_handler;
 pub mod install_handler;
 pub mod list_handler;
@@ -28,14 +30,18 @@ pub enum InstallResult {
 
 /// Represents the type of a version after it has been downloaded.
 ///
-/// This enum has three variants:
+/// This enum has five variants:
 ///
 /// * `None` - No specific version type is assigned.
-/// * `Standard(LocalVersion)` - The version is a standard version. The `LocalVersion` contains the details of the version.
+/// * `Standard(LocalVersion)` - The version is a standard version. The `LocalVersion` contains the details of the downloaded archive, which still has to be expanded.
+/// * `Expanded` - The archive was expanded while it was downloaded, so there is nothing left to expand.
 /// * `Hash` - The version is identified by a hash.
+/// * `Unavailable(String)` - The release exists, but there is no official build to download for the platform. The `String` explains why.
 #[derive(PartialEq, Debug)]
 pub enum PostDownloadVersionType {
     None,
     Standard(LocalVersion),
+    Expanded,
     Hash,
+    Unavailable(String),
 }
@@ -22,7 +22,7 @@ use crate::helpers;
 /// * `Result<()>` - Returns a `Result` that indicates whether the operation was successful or not.
 pub async fn start(version: &str, args: &[String], client: &Client, config: &Config) -> Result<()> {
     // Parse the specified version
-    let version = crate::version::parse_version_type(client, version).await?;
+    let version = crate::version::parse_version_type(client, version, config).await?;
     let downloads_dir = helpers::directories::get_downloads_directory(config).await?;
     let version_path = downloads_dir.join(&version.tag_name);
 
@@ -59,7 +59,7 @@ pub async fn start(client: &Client, config: ConfigFile) -> Result<()> {
     );
 
     use_handler::start(
-        version::parse_version_type(client, trimmed_version).await?,
+        version::parse_version_type(client, trimmed_version, &config.config).await?,
         true,
         client,
         config,
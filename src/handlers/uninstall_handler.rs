@@ -47,8 +47,9 @@ pub async fn start(version: Option<&str>, config: Config) -> Result<()> {
         return uninstall_selections(&client, &config).await;
     };
 
-    let version = helpers::version::parse_version_type(&client, version).await?;
-    if helpers::version::is_version_used(&version.non_parsed_string, &config).await {
+    let version = helpers::version::parse_version_type(&client, version, &config).await?;
+    // Compare the resolved tag, `0.10` may resolve to the `v0.10.4` that is in use
+    if helpers::version::is_version_used(&version.tag_name, &config).await {
         warn!("Switch to a different version before proceeding");
         return Ok(());
     }
@@ -104,14 +105,22 @@ async fn uninstall_selections(client: &Client, config: &Config) -> Result<()> {
     let mut installed_versions: Vec<String> = Vec::new();
 
     while let Some(path) = paths.next_entry().await? {
+        if !path.file_type().await?.is_dir() {
+            continue;
+        }
+
         let name = path.file_name().to_str().unwrap().to_owned();
+        // Hidden directories such as the staging area and the archive cache are bob's own
+        if name.starts_with('.') {
+            continue;
+        }
 
-        let Ok(version) = helpers::version::parse_version_type(client, &name).await else {
+        let Ok(version) = helpers::version::parse_version_type(client, &name, config).await else {
             warn!("Could not parse version from file name: {}", name);
             continue;
         };
 
-        if helpers::version::is_version_used(&version.non_parsed_string, config).await {
+        if helpers::version::is_version_used(&version.tag_name, config).await {
             continue;
         }
         installed_versions.push(version.non_parsed_string);
@@ -157,3 +166,32 @@ async fn uninstall_selections(client: &Client, config: &Config) -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod uninstall_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn partial_version_of_the_used_release_is_kept() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("v0.10.4")).await.unwrap();
+        fs::create_dir_all(root.join("v0.9.5")).await.unwrap();
+        fs::write(root.join("used"), "v0.10.4").await.unwrap();
+        let config = Config {
+            downloads_location: Some(root.to_string_lossy().to_string()),
+            ..Config::default()
+        };
+
+        for version in ["0.10", "~0.10", "v0.10.4"] {
+            start(Some(version), config.clone()).await.unwrap();
+            assert!(
+                root.join("v0.10.4").exists(),
+                "{version} removed the used version"
+            );
+        }
+
+        start(Some("0.9"), config).await.unwrap();
+        assert!(!root.join("v0.9.5").exists());
+    }
+}
@@ -53,7 +53,7 @@ pub async fn start(data: Update, client: &Client, config: ConfigFile) -> Result<
     if data.version.is_none() || data.all {
         let mut did_update = false;
 
-        let stable = crate::version::parse_version_type(client, "stable").await?;
+        let stable = crate::version::parse_version_type(client, "stable", &config.config).await?;
         if is_version_installed(&stable.tag_name, &config.config).await? {
             match install_handler::start(&stable, client, &config).await? {
                 InstallResult::InstallationSuccess(_) => did_update = true,
@@ -64,7 +64,8 @@ pub async fn start(data: Update, client: &Client, config: ConfigFile) -> Result<
         }
 
         if is_version_installed("nightly", &config.config).await? {
-            let nightly = crate::version::parse_version_type(client, "nightly").await?;
+            let nightly =
+                crate::version::parse_version_type(client, "nightly", &config.config).await?;
             match install_handler::start(&nightly, client, &config).await? {
                 InstallResult::InstallationSuccess(_) => did_update = true,
                 InstallResult::NightlyIsUpdated
@@ -80,7 +81,8 @@ pub async fn start(data: Update, client: &Client, config: ConfigFile) -> Result<
         return Ok(());
     }
 
-    let version = crate::version::parse_version_type(client, &data.version.unwrap()).await?;
+    let version =
+        crate::version::parse_version_type(client, &data.version.unwrap(), &config.config).await?;
 
     if !is_version_installed(&version.tag_name, &config.config).await? {
         warn!("{} is not installed.", version.non_parsed_string);
@@ -93,7 +93,7 @@ pub async fn start(
 
 /// Switches to a specified version.
 ///
-/// This function changes the current directory to the downloads directory, writes the version to a file named "used", and if the version is different from the version stored in `version_sync_file_location`, it also writes the version to `version_sync_file_location`.
+/// This function changes the current directory to the downloads directory, remembers the currently used version in a file named "previous", writes the version to a file named "used", and if the version is different from the version stored in `version_sync_file_location`, it also writes the version to `version_sync_file_location`.
 ///
 /// # Arguments
 ///
@@ -145,6 +145,12 @@ pub async fn switch(config: &Config, version: &ParsedVersion) -> Result<()> {
         version.tag_name.clone()
     };
 
+    if let Ok(current_version) = fs::read_to_string("used").await {
+        if current_version != file_version {
+            fs::write("previous", &current_version).await?;
+        }
+    }
+
     fs::write("used", &file_version).await?;
     if let Some(version_sync_file_location) =
         helpers::version::get_version_sync_file_location(config).await?
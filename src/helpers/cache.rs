@@ -0,0 +1,367 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::{
+    config::Config,
+    helpers::{checksum::hash_file, directories},
+};
+
+/// Name of the archive cache directory inside the downloads directory.
+pub const CACHE_DIR: &str = ".cache";
+
+/// Name of the file inside the cache directory that lists the cached archives.
+const INDEX_FILE: &str = "index.json";
+
+/// Represents an archive stored in the cache.
+///
+/// Archives are stored as `<sha256>.<file_format>`, so the same archive is only ever stored once.
+///
+/// # Fields
+///
+/// * `tag_name: String` - The tag of the release the archive belongs to.
+/// * `platform: String` - The platform the archive was built for, for example `linux-x86_64`.
+/// * `sha256: String` - The hex encoded SHA-256 of the archive.
+/// * `file_format: String` - The file format of the archive, for example `tar.gz`.
+/// * `size: u64` - The size of the archive in bytes.
+/// * `cached_at: DateTime<Utc>` - When the archive was added to the cache.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CacheEntry {
+    pub tag_name: String,
+    pub platform: String,
+    pub sha256: String,
+    pub file_format: String,
+    pub size: u64,
+    pub cached_at: DateTime<Utc>,
+}
+
+impl CacheEntry {
+    /// Returns the name of the file the archive is stored as.
+    pub fn file_name(&self) -> String {
+        format!("{}.{}", self.sha256, self.file_format)
+    }
+}
+
+/// Returns whether the archive cache is enabled in the configuration.
+pub fn is_enabled(config: &Config) -> bool {
+    config.enable_archive_cache == Some(true)
+}
+
+/// Asynchronously returns the path of the archive cache directory.
+///
+/// # Arguments
+///
+/// * `config` - The configuration to retrieve the downloads directory from.
+///
+/// # Returns
+///
+/// * `Result<PathBuf>` - Returns the path of the cache directory, it is not created by this function.
+///
+/// # Example
+///
+/// ```rust
+/// let cache_dir = get_cache_directory(&config).await?;
+/// ```
+pub async fn get_cache_directory(config: &Config) -> Result<PathBuf> {
+    Ok(directories::get_downloads_directory(config)
+        .await?
+        .join(CACHE_DIR))
+}
+
+/// Asynchronously reads the list of cached archives.
+///
+/// # Arguments
+///
+/// * `config` - The configuration to retrieve the downloads directory from.
+///
+/// # Returns
+///
+/// * `Result<Vec<CacheEntry>>` - Returns the cached archives, or an empty list if nothing was cached yet.
+///
+/// # Errors
+///
+/// This function will return an error if the index exists but cannot be read or parsed.
+///
+/// # Example
+///
+/// ```rust
+/// let entries = read_index(&config).await?;
+/// ```
+pub async fn read_index(config: &Config) -> Result<Vec<CacheEntry>> {
+    let index = get_cache_directory(config).await?.join(INDEX_FILE);
+    match fs::read_to_string(&index).await {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(anyhow!("Failed to read {}: {error}", index.display())),
+    }
+}
+
+/// Asynchronously writes the list of cached archives.
+async fn write_index(cache_dir: &Path, entries: &[CacheEntry]) -> Result<()> {
+    fs::create_dir_all(cache_dir).await?;
+    fs::write(
+        cache_dir.join(INDEX_FILE),
+        serde_json::to_string_pretty(entries)?,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Asynchronously looks up a cached archive and copies it to `<directory>/<tag_name>.<file_format>`.
+///
+/// The cached archive is hashed before it is used. If it doesn't match its recorded SHA-256 it is removed from the cache and `None` is returned.
+///
+/// # Arguments
+///
+/// * `config` - The configuration to retrieve the downloads directory from.
+/// * `tag_name` - The tag of the release to look up.
+/// * `platform` - The platform of the archive to look up.
+/// * `directory` - The directory the cached archive is copied to.
+///
+/// # Returns
+///
+/// * `Result<Option<CacheEntry>>` - Returns the cache entry if the archive was copied to `directory`, or `None` if it isn't cached.
+///
+/// # Errors
+///
+/// This function will return an error if the index or the cached archive cannot be read, or the archive cannot be copied.
+///
+/// # Example
+///
+/// ```rust
+/// let entry = restore(&config, "v0.10.0", "linux-x86_64", Path::new("/path/to/bob/.staging")).await?;
+/// ```
+pub async fn restore(
+    config: &Config,
+    tag_name: &str,
+    platform: &str,
+    directory: &Path,
+) -> Result<Option<CacheEntry>> {
+    let entries = read_index(config).await?;
+    let Some(entry) = entries
+        .iter()
+        .find(|entry| entry.tag_name == tag_name && entry.platform == platform)
+        .cloned()
+    else {
+        return Ok(None);
+    };
+
+    let cache_dir = get_cache_directory(config).await?;
+    let archive = cache_dir.join(entry.file_name());
+    let is_intact = match hash_file(&archive).await {
+        Ok(hash) => hash == entry.sha256,
+        Err(_) => false,
+    };
+
+    if !is_intact {
+        warn!("Cached archive of {tag_name} is damaged, removing it from the cache");
+        remove_entries(config, |cached| cached == &entry).await?;
+        return Ok(None);
+    }
+
+    // A leftover archive may be a hard link to the cached one, copying onto it would truncate the cache
+    let destination = directory.join(format!("{tag_name}.{}", entry.file_format));
+    let _ = fs::remove_file(&destination).await;
+    fs::copy(&archive, destination).await?;
+    info!("Using cached archive of {tag_name}");
+    Ok(Some(entry))
+}
+
+/// Asynchronously adds an archive to the cache.
+///
+/// The archive is hard linked into the cache if possible and copied otherwise.
+/// Any previously cached archive for the same tag and platform is replaced, and the oldest archives are evicted while the cache is larger than `Config::max_cache_size`.
+///
+/// # Arguments
+///
+/// * `config` - The configuration to retrieve the downloads directory and cache size limit from.
+/// * `archive` - The path of the downloaded archive.
+/// * `entry` - The cache entry describing the archive.
+///
+/// # Returns
+///
+/// * `Result<()>` - Returns a `Result` that indicates whether the operation was successful.
+///
+/// # Example
+///
+/// ```rust
+/// store(&config, Path::new("/path/to/bob/.staging/v0.10.0.tar.gz"), entry).await?;
+/// ```
+pub async fn store(config: &Config, archive: &Path, entry: CacheEntry) -> Result<()> {
+    let cache_dir = get_cache_directory(config).await?;
+    fs::create_dir_all(&cache_dir).await?;
+
+    let cached_archive = cache_dir.join(entry.file_name());
+    if fs::metadata(&cached_archive).await.is_err()
+        && fs::hard_link(archive, &cached_archive).await.is_err()
+    {
+        fs::copy(archive, &cached_archive).await?;
+    }
+
+    let is_same_release = |cached: &CacheEntry| {
+        cached.tag_name == entry.tag_name && cached.platform == entry.platform
+    };
+    remove_entries(config, |cached| {
+        is_same_release(cached) && cached.sha256 != entry.sha256
+    })
+    .await?;
+
+    let mut entries = read_index(config).await?;
+    entries.retain(|cached| !is_same_release(cached));
+    entries.push(entry);
+    write_index(&cache_dir, &entries).await?;
+
+    if let Some(max_size) = config.max_cache_size {
+        prune(config, max_size * 1024 * 1024).await?;
+    }
+
+    Ok(())
+}
+
+/// Asynchronously removes the oldest cached archives until the cache is at most `max_size` bytes.
+async fn prune(config: &Config, max_size: u64) -> Result<()> {
+    let mut entries = read_index(config).await?;
+    entries.sort_by_key(|entry| entry.cached_at);
+
+    let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+    let mut evicted = Vec::new();
+    for entry in &entries {
+        if total <= max_size {
+            break;
+        }
+        total -= entry.size;
+        evicted.push(entry.clone());
+    }
+
+    for entry in &evicted {
+        info!("Evicting {} from the archive cache", entry.tag_name);
+    }
+    remove_entries(config, |entry| evicted.contains(entry)).await?;
+    Ok(())
+}
+
+/// Asynchronously removes every cached archive matching `predicate`.
+///
+/// Archive files are only deleted once no remaining entry refers to them.
+///
+/// # Arguments
+///
+/// * `config` - The configuration to retrieve the downloads directory from.
+/// * `predicate` - A closure selecting the entries to remove.
+///
+/// # Returns
+///
+/// * `Result<Vec<CacheEntry>>` - Returns the removed entries.
+///
+/// # Example
+///
+/// ```rust
+/// let removed = remove_entries(&config, |entry| entry.tag_name == "v0.9.5").await?;
+/// ```
+pub async fn remove_entries(
+    config: &Config,
+    predicate: impl Fn(&CacheEntry) -> bool,
+) -> Result<Vec<CacheEntry>> {
+    let cache_dir = get_cache_directory(config).await?;
+    let (removed, kept): (Vec<CacheEntry>, Vec<CacheEntry>) = read_index(config)
+        .await?
+        .into_iter()
+        .partition(|entry| predicate(entry));
+
+    if removed.is_empty() {
+        return Ok(removed);
+    }
+
+    write_index(&cache_dir, &kept).await?;
+    for entry in &removed {
+        let still_used = kept
+            .iter()
+            .any(|kept| kept.file_name() == entry.file_name());
+        if !still_used {
+            let _ = fs::remove_file(cache_dir.join(entry.file_name())).await;
+        }
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+    use chrono::Duration;
+    use sha2::{Digest, Sha256};
+
+    fn entry(tag_name: &str, content: &[u8], cached_at: DateTime<Utc>) -> CacheEntry {
+        CacheEntry {
+            tag_name: tag_name.to_string(),
+            platform: "linux-x86_64".to_string(),
+            sha256: format!("{:x}", Sha256::digest(content)),
+            file_format: "tar.gz".to_string(),
+            size: content.len() as u64,
+            cached_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn stored_archives_are_restored_and_pruned() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let config = Config {
+            downloads_location: Some(root.to_string_lossy().to_string()),
+            enable_archive_cache: Some(true),
+            max_cache_size: Some(1),
+            ..Config::default()
+        };
+
+        let old = vec![1u8; 600 * 1024];
+        let new = vec![2u8; 600 * 1024];
+        let now = Utc::now();
+        for (tag_name, content, cached_at) in [
+            ("v0.9.5", &old, now - Duration::days(1)),
+            ("v0.10.0", &new, now),
+        ] {
+            let archive = root.join(format!("{tag_name}.tar.gz"));
+            fs::write(&archive, content).await.unwrap();
+            store(&config, &archive, entry(tag_name, content, cached_at))
+                .await
+                .unwrap();
+        }
+
+        // Both archives don't fit in 1 MB, so the older one was evicted
+        let entries = read_index(&config).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].tag_name, "v0.10.0");
+
+        let destination = root.join("v0.10.0.tar.gz");
+        let platform = "linux-x86_64";
+        assert!(
+            restore(&config, "v0.9.5", platform, root)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            restore(&config, "v0.10.0", platform, root)
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(fs::read(&destination).await.unwrap(), new);
+
+        // A damaged archive is dropped instead of being used
+        let cache_dir = get_cache_directory(&config).await.unwrap();
+        fs::write(cache_dir.join(entries[0].file_name()), b"garbage")
+            .await
+            .unwrap();
+        assert!(
+            restore(&config, "v0.10.0", platform, root)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(read_index(&config).await.unwrap().is_empty());
+    }
+}
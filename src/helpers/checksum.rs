@@ -2,32 +2,116 @@ use anyhow::Result;
 use anyhow::anyhow;
 use sha2::{Digest, Sha256};
 use std::path::Path;
-use std::{fs, io};
+use tokio::{fs, io::AsyncReadExt};
 
-/// Checks whether the checksum of the file at path 'a' matches the checksum saved in the file at path 'b'.
+/// Finds the checksum of a file in a checksum manifest such as `shasum.txt` or `<file>.sha256sum`.
 /// # Arguments
 ///
-/// * `a` - A reference to a `&Path` object representing the path of the neovim archive.
-/// * `b` - A reference to a `&Path` object representing the path of the checksum file.
+/// * `manifest` - A reference to a `&str` holding the contents of the checksum manifest.
+/// * `filename` - A reference to a `&str` representing the name of the neovim archive.
 ///
 /// # Returns
 ///
-/// This function returns a `Result` that contains a `bool` indicating whether the checksum of the file at path 'a' matches the checksum saved in the file at path 'b'.
-/// If there is an error opening or reading the files, the function returns `Err(error)`.
-pub fn sha256cmp(a: &Path, b: &Path, filename: &str) -> Result<bool> {
-    let checksum_contents = fs::read_to_string(b)?;
-    let checksum = checksum_contents
+/// This function returns a `Result` that contains the hex encoded checksum of the archive.
+/// If the manifest doesn't list the archive, the function returns `Err(error)`.
+pub fn find_checksum(manifest: &str, filename: &str) -> Result<String> {
+    manifest
         .lines()
         .find(|line| line.contains(filename))
         .and_then(|line| line.split_whitespace().next())
-        .ok_or_else(|| anyhow!("Checksum not found for {filename}"))?;
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Checksum not found for {filename}"))
+}
+
+/// Asynchronously computes the hex encoded SHA-256 of a file.
+/// # Arguments
+///
+/// * `path` - A reference to a `&Path` object representing the file to hash.
+///
+/// # Returns
+///
+/// This function returns a `Result` that contains the hex encoded checksum of the file.
+/// If there is an error opening or reading the file, the function returns `Err(error)`.
+pub async fn hash_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Asynchronously computes the hex encoded SHA-256 of a directory tree.
+///
+/// The hash covers the relative path and the contents of every file, in a fixed order,
+/// so it only changes when a file is added, removed, renamed or edited.
+/// # Arguments
+///
+/// * `path` - A reference to a `&Path` object representing the directory to hash.
+///
+/// # Returns
+///
+/// This function returns a `Result` that contains the hex encoded checksum of the directory.
+/// If there is an error reading the directory or one of its files, the function returns `Err(error)`.
+pub async fn hash_directory(path: &Path) -> Result<String> {
+    let mut files = Vec::new();
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        let mut entries = fs::read_dir(&directory).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                pending.push(entry.path());
+            } else {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
 
     let mut hasher = Sha256::new();
-    let mut file = fs::File::open(a)?;
-    io::copy(&mut file, &mut hasher)?;
+    for file in files {
+        let relative = file
+            .strip_prefix(path)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        hasher.update(relative.as_bytes());
+        hasher.update([0]);
+        hasher.update(hash_file(&file).await?.as_bytes());
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod hash_directory_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn hash_follows_paths_and_contents() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        std::fs::create_dir_all(root.join("cmake")).unwrap();
+        std::fs::write(root.join("CMakeLists.txt"), "project(deps)").unwrap();
+        std::fs::write(root.join("cmake").join("BuildLuajit.cmake"), "v2.1").unwrap();
+
+        let original = hash_directory(root).await.unwrap();
+        assert_eq!(hash_directory(root).await.unwrap(), original);
 
-    let hash = hasher.finalize();
-    let hash = format!("{hash:x}");
+        std::fs::write(root.join("cmake").join("BuildLuajit.cmake"), "v2.2").unwrap();
+        let edited = hash_directory(root).await.unwrap();
+        assert_ne!(edited, original);
 
-    Ok(hash == checksum)
+        std::fs::rename(
+            root.join("cmake").join("BuildLuajit.cmake"),
+            root.join("cmake").join("BuildLuv.cmake"),
+        )
+        .unwrap();
+        assert_ne!(hash_directory(root).await.unwrap(), edited);
+    }
 }
@@ -3,6 +3,7 @@ use std::fs;
 use std::path::PathBuf;
 
 use crate::config::Config;
+use crate::helpers::platform::Platform;
 
 /// Returns the home directory path for the current user.
 ///
@@ -202,3 +203,33 @@ pub async fn get_installation_directory(config: &Config) -> Result<PathBuf> {
         Ok(installation_location)
     }
 }
+
+/// Asynchronously returns the directory that holds the versions installed for another platform.
+///
+/// Versions installed with `bob install --platform` for a platform other than the host can't be used here,
+/// so they are kept apart in `platforms/<platform>` inside the downloads directory, ready to be bundled for that platform.
+///
+/// # Arguments
+///
+/// * `config` - A reference to a `Config` containing the application configuration.
+/// * `platform` - The platform the versions are installed for.
+///
+/// # Returns
+///
+/// This function returns a `Result` that contains a `PathBuf` representing the platform directory path if the operation was successful.
+/// If the operation failed, the function returns `Err` with a description of the error.
+///
+/// # Example
+///
+/// ```rust
+/// let platform: Platform = "linux-arm64".parse()?;
+/// let platform_directory = get_platform_directory(&config, &platform).await?;
+/// ```
+pub async fn get_platform_directory(config: &Config, platform: &Platform) -> Result<PathBuf> {
+    let mut platform_directory = get_downloads_directory(config).await?;
+    platform_directory.push("platforms");
+    platform_directory.push(platform.to_string());
+
+    tokio::fs::create_dir_all(&platform_directory).await?;
+    Ok(platform_directory)
+}
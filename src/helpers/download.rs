@@ -0,0 +1,864 @@
+use anyhow::{Result, anyhow};
+use futures_util::stream::StreamExt;
+use reqwest::{
+    Client, Response, StatusCode,
+    header::{CONTENT_RANGE, ETAG, IF_RANGE, RANGE},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::mpsc,
+};
+use tracing::info;
+
+use crate::helpers::unarchive::{self, ArchiveFormat};
+
+/// Information about a partial download, stored in a sidecar file next to it.
+///
+/// # Fields
+///
+/// * `url: String` - The URL the partial file was downloaded from.
+/// * `etag: String` - The `ETag` the server sent for the file, used to make sure the remote file didn't change.
+/// * `size: u64` - The size of the complete file in bytes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct PartialDownload {
+    url: String,
+    etag: String,
+    size: u64,
+}
+
+/// A download whose response is ready to be written to disk.
+///
+/// The bytes are first written to a partial file. If the transfer is interrupted the partial file and its sidecar are kept,
+/// so the next `request` for the same URL resumes where this one stopped.
+#[derive(Debug)]
+pub struct Download {
+    response: Response,
+    partial: PathBuf,
+    offset: u64,
+    total_size: u64,
+    resumable: bool,
+}
+
+/// The outcome of requesting a download.
+///
+/// # Variants
+///
+/// * `Ready(Download)` - The server answered successfully and the body can be saved.
+/// * `Failed(Response)` - The server answered with an error status, the response is returned for the caller to inspect.
+#[derive(Debug)]
+pub enum DownloadResponse {
+    Ready(Download),
+    Failed(Response),
+}
+
+/// Asynchronously requests `url`, resuming a previous partial download of it if possible.
+///
+/// If `partial` and its sidecar exist and were downloaded from the same URL, the request asks for the remaining bytes with a `Range` header.
+/// The `If-Range` header makes the server send the full file instead if its `ETag` changed, in which case the partial file is discarded.
+///
+/// # Arguments
+///
+/// * `client` - A reference to the HTTP client.
+/// * `url` - The URL to download.
+/// * `partial` - A reference to the path the partial file is kept at while downloading.
+///
+/// # Returns
+///
+/// This function returns a `Result` that contains a `DownloadResponse` if the request was sent successfully.
+/// If the operation failed, the function returns `Err` with a description of the error.
+///
+/// # Example
+///
+/// ```rust
+/// let client = Client::new();
+/// let partial = Path::new("/path/to/bob/.staging/partial/nightly.tar.gz");
+/// if let DownloadResponse::Ready(download) = request(&client, url, partial).await? {
+///     download.save(Path::new("/path/to/bob/.staging/nightly.tar.gz"), None, |_| ()).await?;
+/// }
+/// ```
+pub async fn request(client: &Client, url: &str, partial: &Path) -> Result<DownloadResponse> {
+    if let Some(parent) = partial.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let sidecar = sidecar_path(partial);
+    let resumable = match read_sidecar(&sidecar).await {
+        Some(info) if info.url == url => match fs::metadata(partial).await {
+            Ok(metadata) if metadata.len() > 0 && metadata.len() < info.size => {
+                Some((info, metadata.len()))
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+
+    if let Some((info, offset)) = resumable {
+        let response = client
+            .get(url)
+            .header("user-agent", "bob")
+            .header(RANGE, format!("bytes={offset}-"))
+            .header(IF_RANGE, &info.etag)
+            .send()
+            .await?;
+
+        let resumed = response.status() == StatusCode::PARTIAL_CONTENT
+            && response_etag(&response).is_none_or(|etag| etag == info.etag)
+            && content_range_start(&response) == Some(offset);
+
+        if resumed {
+            info!("Resuming download at {offset} of {} bytes", info.size);
+            return Ok(DownloadResponse::Ready(Download {
+                response,
+                partial: partial.to_path_buf(),
+                offset,
+                total_size: info.size,
+                resumable: true,
+            }));
+        }
+
+        if response.status() == StatusCode::OK {
+            info!("Remote file changed, restarting download");
+            return start(response, url, partial).await;
+        }
+    }
+
+    let response = client.get(url).header("user-agent", "bob").send().await?;
+    start(response, url, partial).await
+}
+
+/// Starts a download from the beginning, discarding any partial file and recording a new sidecar.
+async fn start(response: Response, url: &str, partial: &Path) -> Result<DownloadResponse> {
+    if !response.status().is_success() {
+        return Ok(DownloadResponse::Failed(response));
+    }
+
+    let sidecar = sidecar_path(partial);
+    remove_if_exists(partial).await?;
+    remove_if_exists(&sidecar).await?;
+
+    let total_size = response.content_length().unwrap_or(0);
+    let mut resumable = false;
+
+    // Weak ETags can't be used with `If-Range`, so such downloads are never resumed
+    if let Some(etag) = response_etag(&response).filter(|etag| !etag.starts_with("W/")) {
+        if total_size > 0 {
+            let info = PartialDownload {
+                url: url.to_string(),
+                etag,
+                size: total_size,
+            };
+            fs::write(&sidecar, serde_json::to_string(&info)?).await?;
+            resumable = true;
+        }
+    }
+
+    Ok(DownloadResponse::Ready(Download {
+        response,
+        partial: partial.to_path_buf(),
+        offset: 0,
+        total_size,
+        resumable,
+    }))
+}
+
+impl Download {
+    /// Returns the size of the complete file in bytes, or 0 if the server didn't send it.
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    /// Asynchronously writes the response body to disk and moves the finished file to `destination`.
+    ///
+    /// The SHA-256 of the file is computed from the chunks as they arrive. If `expected_sha256` is set and doesn't match,
+    /// the partial file is removed and the file never reaches `destination`.
+    /// `on_progress` is called with the number of bytes of the complete file that are on disk, including the resumed part.
+    ///
+    /// # Arguments
+    ///
+    /// * `destination` - A reference to the path the finished file is moved to.
+    /// * `expected_sha256` - The expected hex encoded SHA-256 of the complete file, if known.
+    /// * `on_progress` - A closure that receives the download progress.
+    ///
+    /// # Returns
+    ///
+    /// This function returns a `Result` that contains the hex encoded SHA-256 of the downloaded file.
+    /// If the transfer is interrupted, the partial file is kept and the function returns `Err` with a description of the error.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// download.save(Path::new("/path/to/bob/.staging/nightly.tar.gz"), Some(checksum), |position| pb.set_position(position)).await?;
+    /// ```
+    pub async fn save(
+        self,
+        destination: &Path,
+        expected_sha256: Option<&str>,
+        mut on_progress: impl FnMut(u64),
+    ) -> Result<String> {
+        let mut hasher = Sha256::new();
+        if self.offset > 0 {
+            // The resumed part was hashed by the interrupted attempt, which is gone by now
+            let mut existing = fs::File::open(&self.partial).await?;
+            let mut buffer = vec![0; 64 * 1024];
+            loop {
+                let read = existing.read(&mut buffer).await?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+            }
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(self.offset > 0)
+            .truncate(self.offset == 0)
+            .open(&self.partial)
+            .await?;
+
+        let mut downloaded = self.offset;
+        on_progress(downloaded);
+
+        let mut response_bytes = self.response.bytes_stream();
+        while let Some(item) = response_bytes.next().await {
+            let chunk = match item {
+                Ok(chunk) => chunk,
+                Err(error) => {
+                    file.flush().await?;
+                    return Err(anyhow!(
+                        "Download interrupted after {downloaded} bytes, run the command again to resume: {error}"
+                    ));
+                }
+            };
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+            downloaded += chunk.len() as u64;
+            on_progress(downloaded);
+        }
+
+        file.flush().await?;
+        file.sync_all().await?;
+        drop(file);
+
+        if self.total_size > 0 && downloaded != self.total_size {
+            return Err(anyhow!(
+                "Download incomplete, received {downloaded} of {} bytes, run the command again to resume",
+                self.total_size
+            ));
+        }
+
+        let hash = format!("{:x}", hasher.finalize());
+        if let Some(expected) = expected_sha256 {
+            if !hash.eq_ignore_ascii_case(expected) {
+                remove_if_exists(&self.partial).await?;
+                remove_if_exists(&sidecar_path(&self.partial)).await?;
+                return Err(anyhow!("Checksum mismatch!"));
+            }
+        }
+
+        fs::rename(&self.partial, destination).await?;
+        remove_if_exists(&sidecar_path(&self.partial)).await?;
+
+        Ok(hash)
+    }
+}
+
+impl Download {
+    /// Asynchronously expands the response body into `output` while it is being downloaded.
+    ///
+    /// The body is hashed and fed through the decompressor and tar decoder as it arrives, so the archive is never read back from disk.
+    /// The archive is only written to disk when something needs it: if `archive` is set, or if the server supports resuming the download.
+    /// It is then written to the partial file, which is kept if the transfer is interrupted so the next attempt resumes it,
+    /// and moved to `archive` once it is complete, or removed if `archive` isn't set.
+    /// Otherwise nothing but the expanded files is written, and an interrupted download starts over.
+    /// If `expected_sha256` is set and doesn't match, `output` and the partial file are removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The format of the archive, it must be streamable.
+    /// * `output` - A reference to the directory the archive is expanded into.
+    /// * `archive` - The path the complete archive is moved to, or `None` to not keep it.
+    /// * `expected_sha256` - The expected hex encoded SHA-256 of the complete archive, if known.
+    /// * `on_progress` - A closure that receives the download progress.
+    ///
+    /// # Returns
+    ///
+    /// This function returns a `Result` that contains the hex encoded SHA-256 of the downloaded archive.
+    /// If the transfer is interrupted, the partial file (if any) is kept and the function returns `Err` with a description of the error.
+    /// If the archive can't be expanded, the partial file is removed since resuming it would fail the same way.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// download.extract(ArchiveFormat::TarGz, Path::new("/path/to/bob/.staging/nightly"), None, Some(checksum), |position| pb.set_position(position)).await?;
+    /// ```
+    pub async fn extract(
+        self,
+        format: ArchiveFormat,
+        output: &Path,
+        archive: Option<&Path>,
+        expected_sha256: Option<&str>,
+        on_progress: impl FnMut(u64),
+    ) -> Result<String> {
+        let (sender, receiver) = mpsc::channel(32);
+        let expand_to = output.to_path_buf();
+        let extractor = tokio::task::spawn_blocking(move || {
+            unarchive::expand_tar(ChannelReader::new(receiver), format, &expand_to)
+        });
+        let mut pipeline = Pipeline {
+            sender: Some(sender),
+            extractor: Some(extractor),
+            hasher: Sha256::new(),
+            interrupted: false,
+        };
+
+        let partial = self.partial.clone();
+        let sidecar = sidecar_path(&partial);
+        let write_partial = archive.is_some() || self.resumable;
+        let result = pipeline.run(self, write_partial, on_progress).await;
+
+        // Make sure the extractor doesn't touch `output` anymore before anything is cleaned up
+        let extracted = pipeline.finish().await;
+        let hash = match (result, extracted) {
+            (Ok(hash), Ok(())) => hash,
+            (Err(error), _) | (Ok(_), Err(error)) => {
+                if !pipeline.interrupted {
+                    remove_if_exists(&partial).await?;
+                    remove_if_exists(&sidecar).await?;
+                }
+                return Err(error);
+            }
+        };
+
+        if let Some(expected) = expected_sha256 {
+            if !hash.eq_ignore_ascii_case(expected) {
+                let _ = fs::remove_dir_all(output).await;
+                remove_if_exists(&partial).await?;
+                remove_if_exists(&sidecar).await?;
+                return Err(anyhow!("Checksum mismatch!"));
+            }
+        }
+
+        match archive {
+            Some(archive) => fs::rename(&partial, archive).await?,
+            None => remove_if_exists(&partial).await?,
+        }
+        remove_if_exists(&sidecar).await?;
+
+        Ok(hash)
+    }
+}
+
+/// The state shared between the download and the blocking task expanding it.
+struct Pipeline {
+    sender: Option<mpsc::Sender<Vec<u8>>>,
+    extractor: Option<tokio::task::JoinHandle<Result<()>>>,
+    hasher: Sha256,
+    /// Whether the transfer stopped before the whole body arrived, as opposed to the archive failing to expand.
+    interrupted: bool,
+}
+
+impl Pipeline {
+    /// Streams the resumed part and the response body through the hasher and the extractor, returning the hash.
+    ///
+    /// The body is also written to the partial file if `write_partial` is set.
+    async fn run(
+        &mut self,
+        download: Download,
+        write_partial: bool,
+        mut on_progress: impl FnMut(u64),
+    ) -> Result<String> {
+        let mut downloaded = download.offset;
+        on_progress(downloaded);
+
+        if download.offset > 0 {
+            let mut existing = fs::File::open(&download.partial).await?;
+            let mut buffer = vec![0; 64 * 1024];
+            loop {
+                let read = existing.read(&mut buffer).await?;
+                if read == 0 {
+                    break;
+                }
+                self.feed(&buffer[..read]).await?;
+            }
+        }
+
+        let mut file = match write_partial {
+            true => Some(
+                fs::OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(download.offset > 0)
+                    .truncate(download.offset == 0)
+                    .open(&download.partial)
+                    .await?,
+            ),
+            false => None,
+        };
+        let resume_hint = match download.resumable {
+            true => ", run the command again to resume",
+            false => "",
+        };
+
+        let mut response_bytes = download.response.bytes_stream();
+        while let Some(item) = response_bytes.next().await {
+            let chunk = match item {
+                Ok(chunk) => chunk,
+                Err(error) => {
+                    if let Some(file) = &mut file {
+                        file.flush().await?;
+                    }
+                    self.interrupted = true;
+                    return Err(anyhow!(
+                        "Download interrupted after {downloaded} bytes{resume_hint}: {error}"
+                    ));
+                }
+            };
+            if let Some(file) = &mut file {
+                file.write_all(&chunk).await?;
+            }
+            self.feed(&chunk).await?;
+            downloaded += chunk.len() as u64;
+            on_progress(downloaded);
+        }
+
+        if let Some(mut file) = file {
+            file.flush().await?;
+            file.sync_all().await?;
+        }
+
+        if download.total_size > 0 && downloaded != download.total_size {
+            self.interrupted = true;
+            return Err(anyhow!(
+                "Download incomplete, received {downloaded} of {} bytes{resume_hint}",
+                download.total_size
+            ));
+        }
+
+        Ok(format!("{:x}", std::mem::take(&mut self.hasher).finalize()))
+    }
+
+    /// Hashes a chunk and hands it to the extractor.
+    async fn feed(&mut self, chunk: &[u8]) -> Result<()> {
+        self.hasher.update(chunk);
+
+        let Some(sender) = &self.sender else {
+            return Ok(());
+        };
+        if sender.send(chunk.to_vec()).await.is_err() {
+            // The extractor stopped reading, either because the archive ended or because it failed.
+            // Trailing bytes still have to be hashed, so only a failure ends the download early
+            self.sender = None;
+            if let Some(extractor) = self.extractor.take() {
+                extractor.await??;
+            }
+        }
+        Ok(())
+    }
+
+    /// Signals the end of the stream and waits for the extractor to finish.
+    async fn finish(&mut self) -> Result<()> {
+        self.sender = None;
+        match self.extractor.take() {
+            Some(extractor) => extractor.await?,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Adapts the receiving end of the pipeline channel to `std::io::Read` for the blocking extractor.
+struct ChannelReader {
+    receiver: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl ChannelReader {
+    fn new(receiver: mpsc::Receiver<Vec<u8>>) -> ChannelReader {
+        ChannelReader {
+            receiver,
+            chunk: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl std::io::Read for ChannelReader {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let read = buffer.len().min(self.chunk.len() - self.position);
+        buffer[..read].copy_from_slice(&self.chunk[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+/// Returns the path of the sidecar file belonging to a partial download.
+fn sidecar_path(partial: &Path) -> PathBuf {
+    let mut sidecar = partial.as_os_str().to_owned();
+    sidecar.push(".json");
+    PathBuf::from(sidecar)
+}
+
+/// Reads the sidecar of a partial download, returning `None` if it is missing or malformed.
+async fn read_sidecar(sidecar: &Path) -> Option<PartialDownload> {
+    let content = fs::read_to_string(sidecar).await.ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Returns the `ETag` header of a response.
+fn response_etag(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get(ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Returns the first byte position of a `Content-Range: bytes <start>-<end>/<size>` header.
+fn content_range_start(response: &Response) -> Option<u64> {
+    let value = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    let range = value.strip_prefix("bytes ")?;
+    range.split('-').next()?.parse().ok()
+}
+
+/// Removes a file, ignoring it if it doesn't exist.
+async fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path).await {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod download_tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Serves `body` with the given `ETag`, honouring `Range` and `If-Range`.
+    /// The first `truncated` connections are closed halfway through the body.
+    /// Returns the URL and the `Range` header of every request.
+    async fn serve(
+        body: Vec<u8>,
+        etag: &'static str,
+        truncated: usize,
+    ) -> (String, Arc<Mutex<Vec<Option<String>>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/nvim.tar.gz", listener.local_addr().unwrap());
+        let ranges = Arc::new(Mutex::new(Vec::new()));
+        let seen = ranges.clone();
+
+        tokio::spawn(async move {
+            let mut served = 0;
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+
+                let request = String::from_utf8_lossy(&request).to_lowercase();
+                let header = |name: &str| {
+                    request
+                        .lines()
+                        .find_map(|line| line.strip_prefix(&format!("{name}: ")))
+                        .map(str::to_string)
+                };
+                let range = header("range");
+                seen.lock().unwrap().push(range.clone());
+
+                let start = match (range, header("if-range")) {
+                    (Some(range), Some(if_range)) if if_range == etag => range
+                        .trim_start_matches("bytes=")
+                        .trim_end_matches('-')
+                        .parse::<usize>()
+                        .ok(),
+                    _ => None,
+                };
+
+                let head = match start {
+                    Some(start) => format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {start}-{}/{}\r\nETag: {etag}\r\nConnection: close\r\n\r\n",
+                        body.len() - start,
+                        body.len() - 1,
+                        body.len()
+                    ),
+                    None => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nETag: {etag}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    ),
+                };
+                let content = &body[start.unwrap_or(0)..];
+                socket.write_all(head.as_bytes()).await.unwrap();
+
+                served += 1;
+                if served <= truncated {
+                    socket
+                        .write_all(&content[..content.len() / 2])
+                        .await
+                        .unwrap();
+                } else {
+                    socket.write_all(content).await.unwrap();
+                }
+                socket.shutdown().await.ok();
+            }
+        });
+
+        (url, ranges)
+    }
+
+    async fn download(
+        client: &Client,
+        url: &str,
+        dir: &Path,
+        expected_sha256: Option<&str>,
+    ) -> Result<String> {
+        let partial = dir.join("partial").join("nvim.tar.gz");
+        match request(client, url, &partial).await? {
+            DownloadResponse::Ready(download) => {
+                download
+                    .save(&dir.join("nvim.tar.gz"), expected_sha256, |_| ())
+                    .await
+            }
+            DownloadResponse::Failed(response) => Err(anyhow!("{}", response.status())),
+        }
+    }
+
+    #[tokio::test]
+    async fn interrupted_download_is_resumed() {
+        let body: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let (url, ranges) = serve(body.clone(), "\"v1\"", 1).await;
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let client = Client::new();
+
+        let checksum = format!("{:x}", Sha256::digest(&body));
+
+        assert!(download(&client, &url, dir, Some(&checksum)).await.is_err());
+        assert!(dir.join("partial").join("nvim.tar.gz.json").exists());
+
+        download(&client, &url, dir, Some(&checksum)).await.unwrap();
+        assert_eq!(std::fs::read(dir.join("nvim.tar.gz")).unwrap(), body);
+        assert!(!dir.join("partial").join("nvim.tar.gz").exists());
+        assert!(!dir.join("partial").join("nvim.tar.gz.json").exists());
+
+        let ranges = ranges.lock().unwrap();
+        assert_eq!(ranges[0], None);
+        assert!(ranges[1].as_ref().is_some_and(|range| range != "bytes=0-"));
+    }
+
+    #[tokio::test]
+    async fn changed_etag_restarts_download() {
+        let body: Vec<u8> = (0..50_000u32).map(|i| (i % 13) as u8).collect();
+        let (url, ranges) = serve(body.clone(), "\"v2\"", 0).await;
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let client = Client::new();
+
+        let partial = dir.join("partial");
+        std::fs::create_dir_all(&partial).unwrap();
+        std::fs::write(partial.join("nvim.tar.gz"), vec![0xff; 1000]).unwrap();
+        let info = PartialDownload {
+            url: url.clone(),
+            etag: "\"v1\"".to_string(),
+            size: body.len() as u64,
+        };
+        std::fs::write(
+            partial.join("nvim.tar.gz.json"),
+            serde_json::to_string(&info).unwrap(),
+        )
+        .unwrap();
+
+        download(&client, &url, dir, None).await.unwrap();
+        assert_eq!(std::fs::read(dir.join("nvim.tar.gz")).unwrap(), body);
+        assert_eq!(ranges.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn checksum_mismatch_discards_download() {
+        let body = vec![7u8; 10_000];
+        let (url, _) = serve(body, "\"v1\"", 0).await;
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let client = Client::new();
+
+        let error = download(&client, &url, dir, Some(&"0".repeat(64)))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Checksum mismatch"));
+        assert!(!dir.join("nvim.tar.gz").exists());
+        assert!(!dir.join("partial").join("nvim.tar.gz").exists());
+        assert!(!dir.join("partial").join("nvim.tar.gz.json").exists());
+    }
+
+    /// Packs `content` as `bin/nvim` into a gzipped tarball laid out like an official release.
+    fn tarball(content: &[u8]) -> Vec<u8> {
+        use flate2::{Compression, write::GzEncoder};
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o755);
+        builder
+            .append_data(&mut header, "nvim-linux-x86_64/bin/nvim", content)
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn archive_is_expanded_while_downloading() {
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 97) as u8).collect();
+        let body = tarball(&content);
+        let checksum = format!("{:x}", Sha256::digest(&body));
+
+        let (url, _) = serve(body, "\"v1\"", 0).await;
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let client = Client::new();
+        let partial = dir.join("partial").join("nvim.tar.gz");
+
+        for expected in [&"0".repeat(64), &checksum] {
+            let DownloadResponse::Ready(download) = request(&client, &url, &partial).await.unwrap()
+            else {
+                panic!("the test server always answers successfully");
+            };
+            let result = download
+                .extract(
+                    ArchiveFormat::TarGz,
+                    &dir.join("nvim"),
+                    None,
+                    Some(expected),
+                    |_| (),
+                )
+                .await;
+
+            if expected == &checksum {
+                assert_eq!(result.unwrap(), checksum);
+                assert_eq!(std::fs::read(dir.join("nvim/bin/nvim")).unwrap(), content);
+            } else {
+                assert!(
+                    result
+                        .unwrap_err()
+                        .to_string()
+                        .contains("Checksum mismatch")
+                );
+                assert!(!dir.join("nvim").exists());
+            }
+            // The archive is only kept while the download is incomplete
+            assert!(!partial.exists());
+            assert!(!dir.join("partial").join("nvim.tar.gz.json").exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn interrupted_extraction_is_resumed_without_keeping_the_archive() {
+        let content: Vec<u8> = (0..400_000u32).map(|i| (i % 89) as u8).collect();
+        let body = tarball(&content);
+        let checksum = format!("{:x}", Sha256::digest(&body));
+
+        let (url, ranges) = serve(body, "\"v1\"", 1).await;
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let client = Client::new();
+        let partial = dir.join("partial").join("nvim.tar.gz");
+
+        for attempt in 0..2 {
+            // The staging directory is cleaned before every attempt
+            let _ = std::fs::remove_dir_all(dir.join("nvim"));
+            let DownloadResponse::Ready(download) = request(&client, &url, &partial).await.unwrap()
+            else {
+                panic!("the test server always answers successfully");
+            };
+            let result = download
+                .extract(
+                    ArchiveFormat::TarGz,
+                    &dir.join("nvim"),
+                    None,
+                    Some(&checksum),
+                    |_| (),
+                )
+                .await;
+
+            if attempt == 0 {
+                assert!(result.is_err());
+                assert!(partial.exists());
+                assert!(dir.join("partial").join("nvim.tar.gz.json").exists());
+            } else {
+                assert_eq!(result.unwrap(), checksum);
+                assert_eq!(std::fs::read(dir.join("nvim/bin/nvim")).unwrap(), content);
+                assert!(!partial.exists());
+                assert!(!dir.join("partial").join("nvim.tar.gz.json").exists());
+            }
+        }
+
+        let ranges = ranges.lock().unwrap();
+        assert_eq!(ranges[0], None);
+        assert!(ranges[1].as_ref().is_some_and(|range| range != "bytes=0-"));
+    }
+
+    #[tokio::test]
+    async fn archive_is_not_written_when_the_download_cannot_be_resumed() {
+        let content: Vec<u8> = (0..400_000u32).map(|i| (i % 97) as u8).collect();
+        let body = tarball(&content);
+        let checksum = format!("{:x}", Sha256::digest(&body));
+
+        // A weak ETag can't be used to resume the download
+        let (url, ranges) = serve(body, "W/\"v1\"", 1).await;
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let client = Client::new();
+        let partial = dir.join("partial").join("nvim.tar.gz");
+
+        for attempt in 0..2 {
+            let _ = std::fs::remove_dir_all(dir.join("nvim"));
+            let DownloadResponse::Ready(download) = request(&client, &url, &partial).await.unwrap()
+            else {
+                panic!("the test server always answers successfully");
+            };
+            let result = download
+                .extract(
+                    ArchiveFormat::TarGz,
+                    &dir.join("nvim"),
+                    None,
+                    Some(&checksum),
+                    |_| (),
+                )
+                .await;
+
+            if attempt == 0 {
+                let error = result.unwrap_err().to_string();
+                assert!(!error.contains("resume"), "{error}");
+            } else {
+                assert_eq!(result.unwrap(), checksum);
+                assert_eq!(std::fs::read(dir.join("nvim/bin/nvim")).unwrap(), content);
+            }
+            assert!(!partial.exists());
+            assert!(!dir.join("partial").join("nvim.tar.gz.json").exists());
+        }
+
+        assert_eq!(*ranges.lock().unwrap(), vec![None, None]);
+    }
+}
@@ -1,5 +1,4 @@
 use anyhow::{Result, anyhow};
-use async_recursion::async_recursion;
 use indicatif::{ProgressBar, ProgressStyle};
 use std::path::Path;
 use tokio::fs;
@@ -62,55 +61,184 @@ pub async fn remove_dir(directory: &str) -> Result<()> {
     Ok(())
 }
 
-/// Asynchronously copies a directory from one location to another.
+/// Name of the directory inside the downloads directory that versions are staged in before they are moved into place.
+pub const STAGING_DIR: &str = ".staging";
+
+/// Asynchronously prepares the staging directory for a version.
 ///
-/// This function takes two arguments: the source directory and the destination directory. Both arguments are implemented as references to `Path` and are static.
-/// It first creates the destination directory, then reads the entries of the source directory.
-/// For each entry in the source directory, it checks if the entry is a directory or a file.
-/// If the entry is a directory, it recursively calls `copy_dir` to copy the directory to the destination.
-/// If the entry is a file, it copies the file to the destination.
+/// This function creates the staging directory inside `root` if it doesn't exist, and removes anything left behind for `name` by an earlier install that was interrupted.
 ///
 /// # Arguments
 ///
-/// * `from` - A reference to a `Path` representing the source directory.
-/// * `to` - A reference to a `Path` representing the destination directory.
+/// * `root` - A reference to a `Path` representing the downloads directory.
+/// * `name` - The name of the version that is about to be staged.
 ///
 /// # Returns
 ///
-/// This function returns a `Result` that indicates whether the operation was successful.
-/// If the operation was successful, the function returns `Ok(())`.
+/// This function returns a `Result` that contains the path of the staging directory if the operation was successful.
 /// If the operation failed, the function returns `Err` with a description of the error.
 ///
 /// # Example
 ///
 /// ```rust
-/// let from = Path::new("/path/to/source");
-/// let to = Path::new("/path/to/destination");
-/// copy_dir(from, to).await;
+/// let staging = prepare_staging(Path::new("/path/to/bob"), "nightly").await?;
 /// ```
-#[async_recursion(?Send)]
-pub async fn copy_dir_async(
-    from: impl AsRef<Path> + 'static,
-    to: impl AsRef<Path> + 'static,
-) -> Result<()> {
-    let original_path = from.as_ref().to_owned();
-    let destination = to.as_ref().to_owned();
+pub async fn prepare_staging(root: &Path, name: &str) -> Result<std::path::PathBuf> {
+    let staging = root.join(STAGING_DIR);
+    fs::create_dir_all(&staging).await?;
+    clean_staging(root, name).await?;
 
-    fs::create_dir(&destination).await?;
+    Ok(staging)
+}
 
-    let mut entries = fs::read_dir(original_path).await?;
+/// Asynchronously removes everything staged for a version.
+///
+/// This function removes the staged directory of `name` and every file that starts with `name.`, such as downloaded archives and checksum files.
+///
+/// # Arguments
+///
+/// * `root` - A reference to a `Path` representing the downloads directory.
+/// * `name` - The name of the version whose staged files should be removed.
+///
+/// # Returns
+///
+/// This function returns a `Result` that indicates whether the operation was successful.
+/// If the operation failed, the function returns `Err` with a description of the error.
+///
+/// # Example
+///
+/// ```rust
+/// clean_staging(Path::new("/path/to/bob"), "nightly").await?;
+/// ```
+pub async fn clean_staging(root: &Path, name: &str) -> Result<()> {
+    let staging = root.join(STAGING_DIR);
+    let Ok(mut entries) = fs::read_dir(&staging).await else {
+        return Ok(());
+    };
 
+    let prefix = format!("{name}.");
     while let Some(entry) = entries.next_entry().await? {
-        let path = entry.path();
+        let entry_name = entry.file_name().to_string_lossy().to_string();
+        if entry_name != name && !entry_name.starts_with(&prefix) {
+            continue;
+        }
 
-        if path.is_dir() {
-            let new_dest = destination.join(path.file_name().unwrap());
-            copy_dir_async(path, new_dest).await?;
+        if entry.file_type().await?.is_dir() {
+            fs::remove_dir_all(entry.path()).await?;
         } else {
-            let new_dest = destination.join(path.file_name().unwrap());
-            fs::copy(path, new_dest).await?;
+            fs::remove_file(entry.path()).await?;
         }
     }
 
     Ok(())
 }
+
+/// Asynchronously moves a staged directory into place.
+///
+/// This function renames `staged` to `destination`. If `destination` already exists it is first renamed out of the way, either to `previous` when it should be kept (for example as a nightly rollback) or to a temporary backup that is removed once the swap succeeded.
+/// If the staged directory cannot be moved, the original `destination` is restored, so a failed swap never leaves a half-installed version behind.
+///
+/// # Arguments
+///
+/// * `staged` - A reference to a `Path` representing the fully installed staged directory.
+/// * `destination` - A reference to a `Path` representing where the version should end up.
+/// * `previous` - An optional `Path` to keep the replaced directory at.
+///
+/// # Returns
+///
+/// This function returns a `Result` that indicates whether the operation was successful.
+/// If the operation failed, the function returns `Err` with a description of the error.
+///
+/// # Example
+///
+/// ```rust
+/// let staged = Path::new("/path/to/bob/.staging/nightly");
+/// let destination = Path::new("/path/to/bob/nightly");
+/// swap_into_place(staged, destination, None).await?;
+/// ```
+pub async fn swap_into_place(
+    staged: &Path,
+    destination: &Path,
+    previous: Option<&Path>,
+) -> Result<()> {
+    let backup = match previous {
+        Some(previous) => previous.to_path_buf(),
+        None => {
+            let mut backup = staged.as_os_str().to_owned();
+            backup.push(".old");
+            std::path::PathBuf::from(backup)
+        }
+    };
+
+    let has_existing = fs::metadata(destination).await.is_ok();
+    if has_existing {
+        if fs::metadata(&backup).await.is_ok() {
+            fs::remove_dir_all(&backup).await?;
+        }
+        fs::rename(destination, &backup).await?;
+    }
+
+    if let Err(error) = fs::rename(staged, destination).await {
+        if has_existing {
+            fs::rename(&backup, destination).await?;
+        }
+        return Err(anyhow!(
+            "Failed to move {} into place: {error}",
+            destination.display()
+        ));
+    }
+
+    if has_existing && previous.is_none() {
+        fs::remove_dir_all(&backup).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod staging_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn swap_replaces_existing_directory() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let staging = prepare_staging(root, "nightly").await.unwrap();
+
+        let destination = root.join("nightly");
+        fs::create_dir_all(&destination).await.unwrap();
+        fs::write(destination.join("old"), "").await.unwrap();
+
+        let staged = staging.join("nightly");
+        fs::create_dir_all(&staged).await.unwrap();
+        fs::write(staged.join("new"), "").await.unwrap();
+        fs::write(staging.join("nightly.tar.gz"), "").await.unwrap();
+
+        let rollback = root.join("nightly-abcdefg");
+        swap_into_place(&staged, &destination, Some(&rollback))
+            .await
+            .unwrap();
+
+        assert!(destination.join("new").exists());
+        assert!(rollback.join("old").exists());
+        assert!(!staged.exists());
+
+        clean_staging(root, "nightly").await.unwrap();
+        assert!(!staging.join("nightly.tar.gz").exists());
+    }
+
+    #[tokio::test]
+    async fn failed_swap_keeps_existing_directory() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let staging = prepare_staging(root, "v0.10.0").await.unwrap();
+
+        let destination = root.join("v0.10.0");
+        fs::create_dir_all(&destination).await.unwrap();
+        fs::write(destination.join("old"), "").await.unwrap();
+
+        let missing = staging.join("v0.10.0");
+        assert!(swap_into_place(&missing, &destination, None).await.is_err());
+        assert!(destination.join("old").exists());
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::{Result, anyhow};
+use semver::Version;
+use std::fmt;
+
+/// The C library of a Linux host.
+///
+/// # Variants
+///
+/// * `Glibc(Version)` - The GNU C library, with its version.
+/// * `Musl` - The musl C library, used by distributions such as Alpine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Libc {
+    Glibc(Version),
+    Musl,
+}
+
+impl fmt::Display for Libc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Libc::Glibc(version) => write!(f, "glibc {}.{}", version.major, version.minor),
+            Libc::Musl => write!(f, "musl"),
+        }
+    }
+}
+
+/// The oldest glibc the official Linux release archives run on, starting with the Neovim release that raised it.
+///
+/// # Fields
+///
+/// * `since: Version` - The first Neovim release with this requirement.
+/// * `glibc: Version` - The oldest supported glibc.
+struct GlibcRequirement {
+    since: Version,
+    glibc: Version,
+}
+
+/// Known glibc requirements of the official Linux release archives, newest first.
+///
+/// Releases older than the last entry have no known requirement and are not checked.
+const GLIBC_REQUIREMENTS: &[GlibcRequirement] = &[GlibcRequirement {
+    since: Version::new(0, 10, 0),
+    glibc: Version::new(2, 31, 0),
+}];
+
+/// Returns the oldest glibc the official Linux release archive of a version runs on.
+///
+/// # Arguments
+///
+/// * `semver` - The version of the release, `None` for nightly, which follows the newest requirement.
+///
+/// # Returns
+///
+/// * `Option<&'static Version>` - Returns the oldest supported glibc, or `None` if the requirement isn't known.
+pub fn required_glibc(semver: Option<&Version>) -> Option<&'static Version> {
+    GLIBC_REQUIREMENTS
+        .iter()
+        .find(|requirement| semver.is_none_or(|semver| *semver >= requirement.since))
+        .map(|requirement| &requirement.glibc)
+}
+
+/// Detects the C library of the host.
+///
+/// # Returns
+///
+/// * `Option<Libc>` - Returns the C library, or `None` if the host isn't Linux or it can't be detected.
+pub fn detect() -> Option<Libc> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+
+    // musl's ldd prints its version to stderr and exits with an error, so both streams are read
+    let output = std::process::Command::new("ldd")
+        .arg("--version")
+        .output()
+        .ok()?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+
+    parse_ldd_version(&text)
+}
+
+/// Parses the output of `ldd --version`, for example `ldd (Ubuntu GLIBC 2.35-0ubuntu3) 2.35` or `musl libc (x86_64)`.
+fn parse_ldd_version(output: &str) -> Option<Libc> {
+    if output.to_lowercase().contains("musl") {
+        return Some(Libc::Musl);
+    }
+
+    let version = output.lines().next()?.split_whitespace().last()?;
+    let (major, minor) = version.split_once('.')?;
+    let minor: String = minor.chars().take_while(char::is_ascii_digit).collect();
+
+    Some(Libc::Glibc(Version::new(
+        major.parse().ok()?,
+        minor.parse().ok()?,
+        0,
+    )))
+}
+
+/// Checks that the official Linux release archive of a version runs with the C library of the host.
+///
+/// # Arguments
+///
+/// * `tag_name` - The tag of the release, used in the error message.
+/// * `semver` - The version of the release, `None` for nightly.
+/// * `libc` - The C library of the host, `None` if it is unknown, in which case the check passes.
+///
+/// # Returns
+///
+/// * `Result<()>` - Returns `Ok(())` if the release runs on the host or it can't be told.
+///
+/// # Errors
+///
+/// This function will return an error explaining the incompatibility if the host uses musl, or a glibc older than the release needs.
+///
+/// # Example
+///
+/// ```rust
+/// libc::check("v0.10.0", Some(&Version::new(0, 10, 0)), libc::detect().as_ref())?;
+/// ```
+pub fn check(tag_name: &str, semver: Option<&Version>, libc: Option<&Libc>) -> Result<()> {
+    match (libc, required_glibc(semver)) {
+        (Some(Libc::Musl), _) => Err(anyhow!(
+            "The official Neovim {tag_name} build needs glibc, but this system uses musl, so it would fail to start"
+        )),
+        (Some(libc @ Libc::Glibc(host)), Some(required)) if host < required => Err(anyhow!(
+            "The official Neovim {tag_name} build needs glibc {}.{} or newer, but this system has {libc}, so it would fail to start",
+            required.major,
+            required.minor
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod libc_tests {
+    use super::*;
+
+    #[test]
+    fn ldd_output_is_parsed() {
+        assert_eq!(
+            parse_ldd_version("ldd (Ubuntu GLIBC 2.35-0ubuntu3.8) 2.35\nCopyright (C) 2022"),
+            Some(Libc::Glibc(Version::new(2, 35, 0)))
+        );
+        assert_eq!(
+            parse_ldd_version("ldd (GNU libc) 2.17"),
+            Some(Libc::Glibc(Version::new(2, 17, 0)))
+        );
+        assert_eq!(
+            parse_ldd_version("musl libc (x86_64)\nVersion 1.2.4\n"),
+            Some(Libc::Musl)
+        );
+        assert_eq!(parse_ldd_version(""), None);
+    }
+
+    #[test]
+    fn releases_are_checked_against_their_requirement() {
+        let old_glibc = Libc::Glibc(Version::new(2, 28, 0));
+        let new_glibc = Libc::Glibc(Version::new(2, 35, 0));
+        let v0_10 = Version::new(0, 10, 0);
+        let v0_9 = Version::new(0, 9, 5);
+
+        assert!(check("v0.10.0", Some(&v0_10), Some(&new_glibc)).is_ok());
+        assert!(check("v0.9.5", Some(&v0_9), Some(&old_glibc)).is_ok());
+        assert!(check("v0.10.0", Some(&v0_10), None).is_ok());
+        assert_eq!(
+            check("v0.10.0", Some(&v0_10), Some(&old_glibc))
+                .unwrap_err()
+                .to_string(),
+            "The official Neovim v0.10.0 build needs glibc 2.31 or newer, but this system has glibc 2.28, so it would fail to start"
+        );
+        assert!(check("nightly", None, Some(&old_glibc)).is_err());
+        assert!(check("v0.9.5", Some(&v0_9), Some(&Libc::Musl)).is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use anyhow::{Result, anyhow};
+use fs4::fs_std::FileExt;
+use std::{
+    fs::{File, OpenOptions},
+    future::Future,
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+use tracing::info;
+
+/// Name of the lock file inside the downloads directory.
+pub const LOCK_FILE: &str = "bob.lock";
+
+/// How long to wait between attempts to acquire a held lock.
+const RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The lock of the downloads directory held by this process, so `unlocked` can release it for a while.
+static HELD_LOCK: Mutex<Option<(Arc<File>, PathBuf)>> = Mutex::new(None);
+
+/// Determines what happens when another bob process already holds the lock.
+///
+/// # Variants
+///
+/// * `Wait(Duration)` - Keep retrying until the lock is released or the timeout elapses.
+/// * `FailFast` - Return an error immediately.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockMode {
+    Wait(Duration),
+    FailFast,
+}
+
+/// An advisory lock on a lock file, held for as long as the value is alive.
+///
+/// It guards the downloads directory as a whole, or a part of it such as a build worktree.
+/// The lock is an exclusive OS file lock (`flock` on Unix, `LockFileEx` on Windows) on a lock file that stays in place.
+/// The operating system releases it when the value is dropped or the process exits, so a crashed process never leaves a stale lock behind.
+/// The file holds the PID of the process holding the lock, which is only used to tell who is being waited for.
+#[derive(Debug)]
+pub struct FileLock {
+    file: Arc<File>,
+}
+
+impl FileLock {
+    /// Asynchronously acquires the lock of the given downloads directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - A reference to a `Path` representing the downloads directory.
+    /// * `mode` - The `LockMode` deciding whether to wait for another process to release the lock.
+    ///
+    /// # Returns
+    ///
+    /// This function returns a `Result` that contains the held `FileLock` if the operation was successful.
+    /// If the operation failed, the function returns `Err` with a description of the error.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    ///
+    /// * Another process holds the lock and `mode` is `LockMode::FailFast`.
+    /// * Another process still holds the lock after the timeout of `LockMode::Wait` elapsed.
+    /// * The lock file cannot be opened, locked or written.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let lock = FileLock::acquire_downloads(Path::new("/path/to/bob"), LockMode::FailFast).await?;
+    /// ```
+    pub async fn acquire_downloads(root: &Path, mode: LockMode) -> Result<FileLock> {
+        let path = root.join(LOCK_FILE);
+        let lock = FileLock::acquire(&path, mode).await?;
+        *HELD_LOCK.lock().unwrap() = Some((Arc::clone(&lock.file), path));
+        Ok(lock)
+    }
+
+    /// Asynchronously acquires a lock file at an arbitrary path.
+    ///
+    /// This guards a single part of the downloads directory, such as a build worktree, instead of all of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A reference to a `Path` representing the lock file.
+    /// * `mode` - The `LockMode` deciding whether to wait for another process to release the lock.
+    ///
+    /// # Returns
+    ///
+    /// This function returns a `Result` that contains the held `FileLock` if the operation was successful.
+    /// If the operation failed, the function returns `Err` with a description of the error.
+    ///
+    /// # Errors
+    ///
+    /// This function fails in the same cases as `FileLock::acquire_downloads`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let lock = FileLock::acquire(Path::new("/path/to/bob/neovim-git/worktrees/0.lock"), LockMode::FailFast).await?;
+    /// ```
+    pub async fn acquire(path: &Path, mode: LockMode) -> Result<FileLock> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|error| anyhow!("Failed to open lock file {}: {error}", path.display()))?;
+        lock_file(&file, path, mode).await?;
+
+        Ok(FileLock {
+            file: Arc::new(file),
+        })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Closing the last handle of the file releases the lock, the file itself stays
+        let mut held = HELD_LOCK.lock().unwrap();
+        if held
+            .as_ref()
+            .is_some_and(|(file, _)| Arc::ptr_eq(file, &self.file))
+        {
+            *held = None;
+        }
+    }
+}
+
+/// Asynchronously runs `work` while this process doesn't hold the lock of the downloads directory.
+///
+/// Long running work that only touches files nobody else uses, like building Neovim in its own worktree,
+/// releases the lock this way so other bob processes don't have to wait for it. The lock is taken again
+/// once `work` is done, waiting for as long as another process holds it. Without a held lock, `work` simply runs.
+/// Whatever the caller checked or prepared under the lock before may have changed once this returns, so it has to be checked again.
+///
+/// # Arguments
+///
+/// * `work` - The future to run without the lock.
+///
+/// # Returns
+///
+/// This function returns a `Result` that contains the output of `work` once the lock is held again.
+///
+/// # Errors
+///
+/// This function will return an error if the lock cannot be released or taken again.
+///
+/// # Example
+///
+/// ```rust
+/// let built = unlocked(build_in_worktree(&version, &config)).await??;
+/// ```
+pub async fn unlocked<F: Future>(work: F) -> Result<F::Output> {
+    let held = HELD_LOCK.lock().unwrap().clone();
+    let Some((file, path)) = held else {
+        return Ok(work.await);
+    };
+
+    FileExt::unlock(&*file)?;
+    let output = work.await;
+    lock_file(&file, &path, LockMode::Wait(Duration::MAX)).await?;
+
+    Ok(output)
+}
+
+/// Asynchronously takes the OS lock of an open lock file and records the PID of this process in it.
+async fn lock_file(file: &File, path: &Path, mode: LockMode) -> Result<()> {
+    let started = Instant::now();
+    let mut announced = false;
+
+    loop {
+        let locked = FileExt::try_lock_exclusive(file)
+            .map_err(|error| anyhow!("Failed to lock {}: {error}", path.display()))?;
+        if locked {
+            file.set_len(0)?;
+            let mut writer = file;
+            writer.seek(SeekFrom::Start(0))?;
+            writer.write_all(std::process::id().to_string().as_bytes())?;
+            writer.flush()?;
+            return Ok(());
+        }
+
+        let holder = read_holder(path).map_or_else(|| "unknown".to_string(), |pid| pid.to_string());
+        match mode {
+            LockMode::FailFast => {
+                return Err(anyhow!(
+                    "Another bob process (PID {holder}) is using {}",
+                    path.parent().unwrap_or(path).display()
+                ));
+            }
+            LockMode::Wait(timeout) => {
+                if started.elapsed() >= timeout {
+                    return Err(anyhow!(
+                        "Timed out after {}s waiting for bob process {holder} to finish",
+                        timeout.as_secs()
+                    ));
+                }
+
+                if !announced {
+                    info!("Waiting for another bob process (PID {holder}) to finish");
+                    announced = true;
+                }
+                sleep(RETRY_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// Reads the PID stored in a lock file, returning `None` if it is unreadable or malformed.
+///
+/// Windows doesn't let other processes read a locked file, so the holder is only known on Unix.
+fn read_holder(path: &Path) -> Option<u32> {
+    let content = std::fs::read_to_string(path).ok()?;
+    content.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod lock_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn held_lock_fails_fast_and_is_released_on_drop() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+
+        let lock = FileLock::acquire_downloads(root, LockMode::FailFast)
+            .await
+            .unwrap();
+        let error = FileLock::acquire_downloads(root, LockMode::FailFast)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains(&std::process::id().to_string()));
+
+        let error = FileLock::acquire_downloads(root, LockMode::Wait(Duration::from_millis(300)))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Timed out"));
+
+        // Other processes can take the lock while the holder works without it
+        let path = root.join(LOCK_FILE);
+        let taken = unlocked(async { FileLock::acquire(&path, LockMode::FailFast).await.is_ok() })
+            .await
+            .unwrap();
+        assert!(taken);
+        assert!(FileLock::acquire(&path, LockMode::FailFast).await.is_err());
+
+        drop(lock);
+        assert!(root.join(LOCK_FILE).exists());
+        FileLock::acquire_downloads(root, LockMode::FailFast)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn lock_file_left_behind_is_taken() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("worktree.lock");
+        // A process that died left its PID behind, but the OS released its lock
+        std::fs::write(&path, "4294967294").unwrap();
+
+        let lock = FileLock::acquire(&path, LockMode::FailFast).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+
+        drop(lock);
+    }
+}
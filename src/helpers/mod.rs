@@ -1,22 +1,31 @@
+pub mod cache;
 pub mod checksum;
 pub mod directories;
+pub mod download;
 pub mod filesystem;
+pub mod libc;
+pub mod lock;
+pub mod platform;
 pub mod processes;
+pub mod progress;
 pub mod unarchive;
 pub mod version;
+use platform::Platform;
 use semver::Version;
 
 /// Returns the platform-specific name for the Neovim version.
 ///
-/// This function takes an `Option<Version>` as an argument, which represents the version of Neovim to be downloaded.
-/// It checks the target operating system and architecture using the `cfg!` macro and returns a string that corresponds to the appropriate Neovim download for the platform.
+/// Release archives are normally looked up in the release assets with `platform::select_release_archive`, this naming is only used when the assets can't be listed.
+///
+/// This function takes an `Option<Version>` as an argument, which represents the version of Neovim to be downloaded, and the platform it is downloaded for.
 /// For Windows, it returns "nvim-win64".
-/// For macOS, it checks the version of Neovim. If the version is less than or equal to 0.9.5, it returns "nvim-macos". If the target architecture is "aarch64", it returns "nvim-macos-arm64". Otherwise, it returns "nvim-macos-x86_64".
-/// For Linux, it checks the version of Neovim. If the version is less than or equal to 0.10.3, it returns "nvim". If the target architecture is "aarch64", it returns "nvim-linux-arm64". Otherwise, it returns "nvim-linux-x86_64".
+/// For macOS, it checks the version of Neovim. If the version is less than or equal to 0.9.5, it returns "nvim-macos". If the architecture is "aarch64", it returns "nvim-macos-arm64". Otherwise, it returns "nvim-macos-x86_64".
+/// For Linux, it checks the version of Neovim. If the version is less than or equal to 0.10.3, it returns "nvim-linux64". If the architecture is "aarch64", it returns "nvim-linux-arm64". Otherwise, it returns "nvim-linux-x86_64".
 ///
 /// # Arguments
 ///
 /// * `version` - An `Option<Version>` representing the version of Neovim to be downloaded.
+/// * `platform` - The platform Neovim is downloaded for.
 ///
 /// # Returns
 ///
@@ -26,28 +35,20 @@ use semver::Version;
 ///
 /// ```rust
 /// let version = Some(Version::new(0, 9, 5));
-/// let platform_name = get_platform_name(&version);
+/// let platform_name = get_platform_name(version.as_ref(), &Platform::current());
 /// ```
-pub fn get_platform_name(version: Option<&Version>) -> &'static str {
-    let version_ref = version.as_ref();
-
-    let is_macos_legacy = version_ref.is_some_and(|v| **v <= Version::new(0, 9, 5));
-    let is_linux_legacy = version_ref.is_some_and(|v| **v <= Version::new(0, 10, 3));
+pub fn get_platform_name(version: Option<&Version>, platform: &Platform) -> &'static str {
+    let is_macos_legacy = version.is_some_and(|v| *v <= Version::new(0, 9, 5));
+    let is_linux_legacy = version.is_some_and(|v| *v <= Version::new(0, 10, 3));
 
-    if cfg!(target_os = "windows") {
-        "nvim-win64"
-    } else if cfg!(target_os = "macos") && is_macos_legacy {
-        "nvim-macos"
-    } else if cfg!(target_os = "macos") && cfg!(target_arch = "aarch64") {
-        "nvim-macos-arm64"
-    } else if cfg!(target_os = "macos") {
-        "nvim-macos-x86_64"
-    } else if is_linux_legacy {
-        "nvim-linux64"
-    } else if cfg!(target_arch = "aarch64") {
-        "nvim-linux-arm64"
-    } else {
-        "nvim-linux-x86_64"
+    match (platform.os, platform.arch) {
+        ("windows", _) => "nvim-win64",
+        ("macos", _) if is_macos_legacy => "nvim-macos",
+        ("macos", "aarch64") => "nvim-macos-arm64",
+        ("macos", _) => "nvim-macos-x86_64",
+        _ if is_linux_legacy => "nvim-linux64",
+        (_, "aarch64") => "nvim-linux-arm64",
+        _ => "nvim-linux-x86_64",
     }
 }
 
@@ -55,46 +56,73 @@ pub fn get_platform_name(version: Option<&Version>) -> &'static str {
 mod tests {
     use super::*;
 
+    const WINDOWS: Platform = Platform {
+        os: "windows",
+        arch: "x86_64",
+    };
+    const MACOS_ARM64: Platform = Platform {
+        os: "macos",
+        arch: "aarch64",
+    };
+    const MACOS_X86_64: Platform = Platform {
+        os: "macos",
+        arch: "x86_64",
+    };
+    const LINUX_ARM64: Platform = Platform {
+        os: "linux",
+        arch: "aarch64",
+    };
+    const LINUX_X86_64: Platform = Platform {
+        os: "linux",
+        arch: "x86_64",
+    };
+
     #[test]
     fn get_platform_name_none() {
-        if cfg!(target_os = "windows") {
-            assert_eq!(get_platform_name(None), "nvim-win64");
-        } else if cfg!(target_os = "macos") && cfg!(target_arch = "aarch64") {
-            assert_eq!(get_platform_name(None), "nvim-macos-arm64");
-        } else if cfg!(target_os = "macos") && cfg!(target_arch = "x86_64") {
-            assert_eq!(get_platform_name(None), "nvim-macos-x86_64");
-        } else if cfg!(target_arch = "aarch64") {
-            assert_eq!(get_platform_name(None), "nvim-linux-arm64");
-        } else {
-            assert_eq!(get_platform_name(None), "nvim-linux-x86_64");
-        }
+        assert_eq!(get_platform_name(None, &WINDOWS), "nvim-win64");
+        assert_eq!(get_platform_name(None, &MACOS_ARM64), "nvim-macos-arm64");
+        assert_eq!(get_platform_name(None, &MACOS_X86_64), "nvim-macos-x86_64");
+        assert_eq!(get_platform_name(None, &LINUX_ARM64), "nvim-linux-arm64");
+        assert_eq!(get_platform_name(None, &LINUX_X86_64), "nvim-linux-x86_64");
     }
 
     #[test]
     fn get_platform_name_lower() {
         let version = Some(semver::Version::new(0, 9, 5));
-        if cfg!(target_os = "windows") {
-            assert_eq!(get_platform_name(version.as_ref()), "nvim-win64");
-        } else if cfg!(target_os = "macos") {
-            assert_eq!(get_platform_name(version.as_ref()), "nvim-macos");
-        } else {
-            assert_eq!(get_platform_name(version.as_ref()), "nvim-linux64");
-        }
+        assert_eq!(get_platform_name(version.as_ref(), &WINDOWS), "nvim-win64");
+        assert_eq!(
+            get_platform_name(version.as_ref(), &MACOS_ARM64),
+            "nvim-macos"
+        );
+        assert_eq!(
+            get_platform_name(version.as_ref(), &MACOS_X86_64),
+            "nvim-macos"
+        );
+        assert_eq!(
+            get_platform_name(version.as_ref(), &LINUX_X86_64),
+            "nvim-linux64"
+        );
     }
 
     #[test]
     fn get_platform_name_higher() {
         let version = Some(semver::Version::new(0, 10, 5));
-        if cfg!(target_os = "windows") {
-            assert_eq!(get_platform_name(version.as_ref()), "nvim-win64");
-        } else if cfg!(target_os = "macos") && cfg!(target_arch = "aarch64") {
-            assert_eq!(get_platform_name(version.as_ref()), "nvim-macos-arm64");
-        } else if cfg!(target_os = "macos") && cfg!(target_arch = "x86_64") {
-            assert_eq!(get_platform_name(version.as_ref()), "nvim-macos-x86_64");
-        } else if cfg!(target_arch = "aarch64") {
-            assert_eq!(get_platform_name(version.as_ref()), "nvim-linux-arm64");
-        } else {
-            assert_eq!(get_platform_name(version.as_ref()), "nvim-linux-x86_64");
-        }
+        assert_eq!(get_platform_name(version.as_ref(), &WINDOWS), "nvim-win64");
+        assert_eq!(
+            get_platform_name(version.as_ref(), &MACOS_ARM64),
+            "nvim-macos-arm64"
+        );
+        assert_eq!(
+            get_platform_name(version.as_ref(), &MACOS_X86_64),
+            "nvim-macos-x86_64"
+        );
+        assert_eq!(
+            get_platform_name(version.as_ref(), &LINUX_ARM64),
+            "nvim-linux-arm64"
+        );
+        assert_eq!(
+            get_platform_name(version.as_ref(), &LINUX_X86_64),
+            "nvim-linux-x86_64"
+        );
     }
 }
@@ -0,0 +1,439 @@
+use anyhow::{Result, anyhow};
+use semver::Version;
+use std::{fmt, str::FromStr, sync::OnceLock};
+
+use crate::{
+    github_requests::ReleaseAsset,
+    helpers::{get_platform_name, unarchive::ArchiveFormat},
+};
+
+/// An operating system and CPU architecture pair, named like Rust's `std::env::consts`.
+///
+/// # Fields
+///
+/// * `os: &'static str` - The operating system, for example `linux`, `macos` or `windows`.
+/// * `arch: &'static str` - The CPU architecture, for example `x86_64` or `aarch64`.
+///
+/// # Example
+///
+/// ```rust
+/// let platform = Platform::current();
+/// println!("Installing Neovim for {platform}");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+    pub os: &'static str,
+    pub arch: &'static str,
+}
+
+impl Platform {
+    /// Returns the platform of the host bob is running on.
+    ///
+    /// The architecture is asked from the operating system at runtime instead of using the one bob was built for,
+    /// so an x86_64 bob running under emulation on an arm64 host still installs the arm64 Neovim.
+    pub fn current() -> Platform {
+        static CURRENT: OnceLock<Platform> = OnceLock::new();
+
+        *CURRENT.get_or_init(|| {
+            let os = std::env::consts::OS;
+            let arch = host_arch()
+                .as_deref()
+                .and_then(normalize_arch)
+                .unwrap_or(std::env::consts::ARCH);
+            Platform { os, arch }
+        })
+    }
+
+    /// Returns `true` if this is the platform of the host bob is running on.
+    pub fn is_current(&self) -> bool {
+        *self == Platform::current()
+    }
+
+    /// Returns the base names of the release archives Neovim published for this platform, newest naming first.
+    pub fn asset_names(&self) -> &'static [&'static str] {
+        ASSET_NAMES
+            .iter()
+            .find(|entry| entry.os == self.os && entry.arch == self.arch)
+            .map_or(&[], |entry| entry.names)
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arch = match self.arch {
+            "aarch64" => "arm64",
+            arch => arch,
+        };
+        write!(f, "{}-{arch}", self.os)
+    }
+}
+
+impl FromStr for Platform {
+    type Err = anyhow::Error;
+
+    /// Parses a platform such as `linux-arm64` or `macos-x86_64`, the architecture may also be spelled `aarch64`, `amd64` or `x64`.
+    fn from_str(value: &str) -> Result<Platform> {
+        let lowercase = value.to_lowercase();
+        let (os, arch) = lowercase.split_once('-').unwrap_or((&lowercase, ""));
+        let os = match os {
+            "darwin" | "osx" => "macos",
+            "win" => "windows",
+            os => os,
+        };
+
+        normalize_arch(arch)
+            .and_then(|arch| {
+                ASSET_NAMES
+                    .iter()
+                    .find(|entry| entry.os == os && entry.arch == arch)
+            })
+            .map(|entry| Platform {
+                os: entry.os,
+                arch: entry.arch,
+            })
+            .ok_or_else(|| {
+                let supported: Vec<String> = ASSET_NAMES
+                    .iter()
+                    .map(|entry| {
+                        Platform {
+                            os: entry.os,
+                            arch: entry.arch,
+                        }
+                        .to_string()
+                    })
+                    .collect();
+                anyhow!(
+                    "Unknown platform {value}, expected one of {}",
+                    supported.join(", ")
+                )
+            })
+    }
+}
+
+/// Maps the names operating systems use for an architecture to the name used by `std::env::consts::ARCH`.
+fn normalize_arch(arch: &str) -> Option<&'static str> {
+    match arch.to_lowercase().as_str() {
+        "x86_64" | "amd64" | "x64" => Some("x86_64"),
+        "aarch64" | "arm64" => Some("aarch64"),
+        _ => None,
+    }
+}
+
+/// Asks the operating system for the architecture of the host.
+///
+/// On macOS a process translated by Rosetta 2 sees `x86_64` from `uname`, so `sysctl.proc_translated` is checked first.
+#[cfg(unix)]
+fn host_arch() -> Option<String> {
+    if cfg!(target_os = "macos") {
+        let translated = std::process::Command::new("sysctl")
+            .args(["-in", "sysctl.proc_translated"])
+            .output()
+            .ok()?;
+        if String::from_utf8_lossy(&translated.stdout).trim() == "1" {
+            return Some("arm64".to_string());
+        }
+    }
+
+    let uname = nix::sys::utsname::uname().ok()?;
+    Some(uname.machine().to_string_lossy().into_owned())
+}
+
+/// Asks the operating system for the architecture of the host.
+///
+/// `PROCESSOR_ARCHITECTURE` of an emulated process reports the emulated architecture, so the system wide value is read from the registry.
+#[cfg(windows)]
+fn host_arch() -> Option<String> {
+    use winreg::RegKey;
+    use winreg::enums::*;
+
+    RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey("SYSTEM\\CurrentControlSet\\Control\\Session Manager\\Environment")
+        .and_then(|key| key.get_value("PROCESSOR_ARCHITECTURE"))
+        .ok()
+}
+
+/// The base names a platform's release archive has been published under.
+struct AssetNames {
+    os: &'static str,
+    arch: &'static str,
+    names: &'static [&'static str],
+}
+
+/// Release archive names for every platform Neovim publishes builds for.
+///
+/// Names are tried in order, so when upstream renames an archive the new name goes first and old releases keep working.
+const ASSET_NAMES: &[AssetNames] = &[
+    AssetNames {
+        os: "linux",
+        arch: "x86_64",
+        names: &["nvim-linux-x86_64", "nvim-linux64"],
+    },
+    AssetNames {
+        os: "linux",
+        arch: "aarch64",
+        names: &["nvim-linux-arm64"],
+    },
+    AssetNames {
+        os: "macos",
+        arch: "aarch64",
+        names: &["nvim-macos-arm64", "nvim-macos"],
+    },
+    AssetNames {
+        os: "macos",
+        arch: "x86_64",
+        names: &["nvim-macos-x86_64", "nvim-macos", "nvim-osx64"],
+    },
+    AssetNames {
+        os: "windows",
+        arch: "x86_64",
+        names: &["nvim-win64"],
+    },
+    AssetNames {
+        os: "windows",
+        arch: "aarch64",
+        names: &["nvim-win-arm64"],
+    },
+];
+
+/// The files of a release needed to install it on one platform.
+///
+/// # Fields
+///
+/// * `archive: ReleaseAsset` - The archive containing Neovim.
+/// * `format: ArchiveFormat` - The format of the archive, taken from its name.
+/// * `checksum: Option<ReleaseAsset>` - The file listing the SHA-256 of the archive, if the release has one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReleaseArchive {
+    pub archive: ReleaseAsset,
+    pub format: ArchiveFormat,
+    pub checksum: Option<ReleaseAsset>,
+}
+
+/// Selects the archive and checksum of a release for a platform from the release's assets.
+///
+/// Archives are matched against the names in `ASSET_NAMES` with every supported archive format.
+/// The checksum is either the archive's own `<archive>.sha256sum` or the release wide `shasum.txt`.
+///
+/// # Arguments
+///
+/// * `assets` - The assets of the release.
+/// * `platform` - The platform to select the archive for.
+/// * `tag_name` - The tag of the release, used in the error message.
+///
+/// # Returns
+///
+/// * `Result<ReleaseArchive>` - Returns the selected files.
+///
+/// # Errors
+///
+/// This function will return an error if the release has no archive for the platform. The error lists the assets the release does have.
+///
+/// # Example
+///
+/// ```rust
+/// if let Some(upstream) = get_upstream_release(&client, "v0.10.0").await? {
+///     let release = select_release_archive(&upstream.assets, &Platform::current(), "v0.10.0")?;
+/// }
+/// ```
+pub fn select_release_archive(
+    assets: &[ReleaseAsset],
+    platform: &Platform,
+    tag_name: &str,
+) -> Result<ReleaseArchive> {
+    let find = |name: &str| assets.iter().find(|asset| asset.name == name).cloned();
+
+    for name in platform.asset_names() {
+        for format in ArchiveFormat::ALL {
+            let file_name = format!("{name}.{format}");
+            if let Some(archive) = find(&file_name) {
+                let checksum =
+                    find(&format!("{file_name}.sha256sum")).or_else(|| find("shasum.txt"));
+                return Ok(ReleaseArchive {
+                    archive,
+                    format,
+                    checksum,
+                });
+            }
+        }
+    }
+
+    let available: Vec<&str> = assets.iter().map(|asset| asset.name.as_str()).collect();
+    Err(anyhow!(
+        "Neovim {tag_name} has no release archive for {platform}, the release contains: {}",
+        if available.is_empty() {
+            "nothing".to_string()
+        } else {
+            available.join(", ")
+        }
+    ))
+}
+
+/// Guesses the archive and checksum of an official release for a platform.
+///
+/// This follows the naming Neovim used when this bob release was made. It is only used when the release assets can't be listed,
+/// for example when the GitHub API is rate limited or only a mirror of the downloads is reachable.
+///
+/// # Arguments
+///
+/// * `tag_name` - The tag of the release.
+/// * `semver` - The version of the release, `None` for nightly.
+/// * `platform` - The platform to guess the archive for.
+///
+/// # Returns
+///
+/// * `ReleaseArchive` - Returns the guessed files, with download URLs on github.com.
+///
+/// # Example
+///
+/// ```rust
+/// let release = guess_release_archive("v0.10.0", Some(&Version::new(0, 10, 0)), &Platform::current());
+/// ```
+pub fn guess_release_archive(
+    tag_name: &str,
+    semver: Option<&Version>,
+    platform: &Platform,
+) -> ReleaseArchive {
+    let format = if platform.os == "windows" {
+        ArchiveFormat::Zip
+    } else {
+        ArchiveFormat::TarGz
+    };
+    let file_name = format!("{}.{format}", get_platform_name(semver, platform));
+    let asset = |name: String| ReleaseAsset {
+        browser_download_url: format!(
+            "https://github.com/neovim/neovim/releases/download/{tag_name}/{name}"
+        ),
+        name,
+        size: 0,
+    };
+
+    let checksum = match semver {
+        Some(semver) if semver <= &Version::new(0, 4, 4) => None,
+        Some(semver) if semver <= &Version::new(0, 10, 4) => {
+            Some(asset(format!("{file_name}.sha256sum")))
+        }
+        _ => Some(asset("shasum.txt".to_string())),
+    };
+
+    ReleaseArchive {
+        archive: asset(file_name),
+        format,
+        checksum,
+    }
+}
+
+#[cfg(test)]
+mod platform_tests {
+    use super::*;
+
+    fn assets(names: &[&str]) -> Vec<ReleaseAsset> {
+        names
+            .iter()
+            .map(|name| ReleaseAsset {
+                name: name.to_string(),
+                browser_download_url: format!("https://example.com/{name}"),
+                size: 1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn newest_asset_name_and_checksum_are_selected() {
+        let linux = Platform {
+            os: "linux",
+            arch: "x86_64",
+        };
+
+        let release = assets(&[
+            "nvim-linux-x86_64.appimage",
+            "nvim-linux-x86_64.tar.gz",
+            "nvim-linux64.tar.gz",
+            "shasum.txt",
+        ]);
+        let selected = select_release_archive(&release, &linux, "v0.11.0").unwrap();
+        assert_eq!(selected.archive.name, "nvim-linux-x86_64.tar.gz");
+        assert_eq!(selected.format, ArchiveFormat::TarGz);
+        assert_eq!(selected.checksum.unwrap().name, "shasum.txt");
+
+        let release = assets(&["nvim-linux64.tar.gz", "nvim-linux64.tar.gz.sha256sum"]);
+        let selected = select_release_archive(&release, &linux, "v0.9.5").unwrap();
+        assert_eq!(
+            selected.checksum.unwrap().name,
+            "nvim-linux64.tar.gz.sha256sum"
+        );
+    }
+
+    #[test]
+    fn guessed_archive_uses_the_official_naming() {
+        let linux = Platform {
+            os: "linux",
+            arch: "x86_64",
+        };
+        let release = guess_release_archive("v0.10.0", Some(&Version::new(0, 10, 0)), &linux);
+        assert_eq!(release.archive.name, "nvim-linux64.tar.gz");
+        assert_eq!(
+            release.archive.browser_download_url,
+            "https://github.com/neovim/neovim/releases/download/v0.10.0/nvim-linux64.tar.gz"
+        );
+        assert_eq!(
+            release.checksum.unwrap().name,
+            "nvim-linux64.tar.gz.sha256sum"
+        );
+
+        let windows = Platform {
+            os: "windows",
+            arch: "x86_64",
+        };
+        let release = guess_release_archive("nightly", None, &windows);
+        assert_eq!(release.archive.name, "nvim-win64.zip");
+        assert_eq!(release.format, ArchiveFormat::Zip);
+        assert_eq!(release.checksum.unwrap().name, "shasum.txt");
+
+        let release = guess_release_archive("v0.4.4", Some(&Version::new(0, 4, 4)), &linux);
+        assert!(release.checksum.is_none());
+    }
+
+    #[test]
+    fn platforms_are_parsed_and_displayed() {
+        for (value, expected) in [
+            ("linux-arm64", "linux-arm64"),
+            ("linux-aarch64", "linux-arm64"),
+            ("Linux-AMD64", "linux-x86_64"),
+            ("darwin-arm64", "macos-arm64"),
+            ("macos-x86_64", "macos-x86_64"),
+            ("win-x64", "windows-x86_64"),
+        ] {
+            assert_eq!(value.parse::<Platform>().unwrap().to_string(), expected);
+        }
+
+        assert_eq!(
+            "linux-arm64".parse::<Platform>().unwrap().asset_names(),
+            ["nvim-linux-arm64"]
+        );
+        assert!(
+            "freebsd-x86_64"
+                .parse::<Platform>()
+                .unwrap_err()
+                .to_string()
+                .starts_with(
+                    "Unknown platform freebsd-x86_64, expected one of linux-x86_64, linux-arm64"
+                )
+        );
+        assert!("linux".parse::<Platform>().is_err());
+    }
+
+    #[test]
+    fn missing_platform_is_reported() {
+        let release = assets(&["nvim-win64.zip", "nvim-win64.msi"]);
+        let platform = Platform {
+            os: "linux",
+            arch: "aarch64",
+        };
+
+        let error = select_release_archive(&release, &platform, "v0.9.0").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Neovim v0.9.0 has no release archive for linux-arm64, the release contains: nvim-win64.zip, nvim-win64.msi"
+        );
+    }
+}
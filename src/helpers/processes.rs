@@ -6,6 +6,7 @@ use tokio::{process::Command, time::sleep};
 
 use crate::helpers::{
     directories, get_platform_name,
+    platform::Platform,
     version::{self},
 };
 
@@ -51,7 +52,8 @@ pub async fn handle_subprocess(process: &mut Command) -> Result<()> {
 /// Handles the execution of the Neovim process.
 ///
 /// This function takes a reference to a `Config` struct and a slice of `String` arguments.
-/// It retrieves the downloads directory and the currently used version of Neovim from the configuration.
+/// It retrieves the downloads directory and the version of Neovim to run: the one pinned by the closest `.nvim-version` or `.bob-version` file,
+/// which `get_project_version` already resolved to the name of its installed directory, or the globally used version otherwise, shortened to 7 characters if it is a full commit hash.
 /// It then constructs the path to the Neovim binary and executes it with the given arguments.
 ///
 /// On Unix systems, this function uses `exec` to replace the current process with Neovim.
@@ -75,6 +77,7 @@ pub async fn handle_subprocess(process: &mut Command) -> Result<()> {
 ///
 /// This function will return an error if:
 ///
+/// * The pinned version cannot be resolved or is not installed.
 /// * The Neovim process exits with a non-zero status code.
 /// * The Neovim process is terminated by a signal.
 /// * The function fails to wait on the child process.
@@ -88,15 +91,22 @@ pub async fn handle_subprocess(process: &mut Command) -> Result<()> {
 /// ```
 pub async fn handle_nvim_process(config: &Config, args: &[String]) -> Result<()> {
     let downloads_dir = directories::get_downloads_directory(config).await?;
-    let used_version = version::get_current_version(config).await?;
+    let project_version = version::get_project_version(config).await?;
+    let is_project_version = project_version.is_some();
+    let used_version = match project_version {
+        Some(project_version) => project_version,
+        None => version::get_current_version(config).await?,
+    };
     let version = semver::Version::parse(&used_version.replace('v', "")).ok();
-    let platform = get_platform_name(version.as_ref());
+    let platform = get_platform_name(version.as_ref(), &Platform::current());
 
-    let new_version: String = if crate::HASH_REGEX.is_match(&used_version) {
-        used_version.chars().take(7).collect()
-    } else {
-        used_version
-    };
+    let new_version = version_directory(used_version, is_project_version);
+
+    if is_project_version && !downloads_dir.join(&new_version).exists() {
+        return Err(anyhow!(
+            "Version {new_version} is pinned for this project but is not installed. Install it first with: bob install {new_version}"
+        ));
+    }
 
     let mut location = downloads_dir.join(&new_version).join("bin").join("nvim");
 
@@ -148,6 +158,18 @@ pub async fn handle_nvim_process(config: &Config, args: &[String]) -> Result<()>
     }
 }
 
+/// Returns the name of the directory the version run by the proxy is installed in.
+///
+/// A project version was already resolved to it by `get_project_version`, so it is used as is.
+/// The used version is only shortened if it is a full commit hash, names such as `pr-12345` or `nightly-abcdef1` are kept.
+fn version_directory(used_version: String, is_project_version: bool) -> String {
+    if !is_project_version && version::is_hash(&used_version) {
+        return used_version.chars().take(7).collect();
+    }
+
+    used_version
+}
+
 pub fn is_neovim_running() -> bool {
     let sys = System::new_all();
 
@@ -159,3 +181,28 @@ pub fn is_neovim_running() -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod nvim_process_tests {
+    use super::version_directory;
+
+    #[test]
+    fn installed_names_are_not_shortened() {
+        for is_project_version in [true, false] {
+            for name in ["pr-12345", "nightly-abcdef1", "user-neovim-fix", "v0.10.4"] {
+                assert_eq!(
+                    version_directory(name.to_string(), is_project_version),
+                    name
+                );
+            }
+        }
+
+        assert_eq!(
+            version_directory(
+                "a1b2c3d4e5f60718293a4b5c6d7e8f9012345678".to_string(),
+                false
+            ),
+            "a1b2c3d"
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{borrow::Cow, io::Read};
+
+/// Template of byte based progress bars, used for downloads and extraction.
+const BYTES_TEMPLATE: &str = "{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})";
+
+/// Template of count based progress bars, used when only the number of items is known.
+const COUNT_TEMPLATE: &str =
+    "{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len}";
+
+/// A progress bar shared by every step of an install, so downloading, verifying and expanding look the same.
+///
+/// The bar can be cloned and moved into blocking tasks, all clones drive the same bar.
+///
+/// # Example
+///
+/// ```rust
+/// let pbw = PbWrapper::new(total_size, "Downloading v0.10.0");
+/// pbw.set_position(1024);
+/// pbw.finish("Downloaded version v0.10.0");
+/// ```
+#[derive(Clone)]
+pub struct PbWrapper {
+    pb: ProgressBar,
+}
+
+impl PbWrapper {
+    /// Creates a progress bar measured in bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `total_size` - The total number of bytes.
+    /// * `message` - The message shown above the bar.
+    pub fn new(total_size: u64, message: impl Into<Cow<'static, str>>) -> PbWrapper {
+        PbWrapper::with_template(total_size, BYTES_TEMPLATE, message)
+    }
+
+    /// Creates a progress bar measured in items, for example archive entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `total` - The total number of items.
+    /// * `message` - The message shown above the bar.
+    pub fn with_count(total: u64, message: impl Into<Cow<'static, str>>) -> PbWrapper {
+        PbWrapper::with_template(total, COUNT_TEMPLATE, message)
+    }
+
+    fn with_template(
+        total: u64,
+        template: &str,
+        message: impl Into<Cow<'static, str>>,
+    ) -> PbWrapper {
+        let pb = ProgressBar::new(total);
+        pb.set_style(
+            ProgressStyle::with_template(template)
+                .expect("progress bar templates are valid")
+                .progress_chars("█  "),
+        );
+        pb.set_message(message);
+        PbWrapper { pb }
+    }
+
+    /// Sets the current position of the bar.
+    pub fn set_position(&self, position: u64) {
+        self.pb.set_position(position);
+    }
+
+    /// Wraps a reader so that every byte read from it advances the bar.
+    pub fn wrap_read<R: Read>(&self, reader: R) -> impl Read {
+        self.pb.wrap_read(reader)
+    }
+
+    /// Completes the bar and replaces its message.
+    pub fn finish(&self, message: impl Into<Cow<'static, str>>) {
+        self.pb.finish_with_message(message);
+    }
+}
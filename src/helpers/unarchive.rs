@@ -1,11 +1,125 @@
 use anyhow::{Result, anyhow};
 use std::{
-    fs,
+    fmt,
+    fs::{self, File},
+    io::{self, Read},
     path::{Path, PathBuf},
 };
 
+use crate::helpers::progress::PbWrapper;
 use crate::version::types::LocalVersion;
 
+/// The archive formats bob can expand, independent of the platform it runs on.
+///
+/// # Variants
+///
+/// * `TarGz` - A gzip compressed tarball, used by the official Linux and macOS releases.
+/// * `TarXz` - An xz compressed tarball.
+/// * `TarZst` - A zstd compressed tarball.
+/// * `Zip` - A zip archive, used by the official Windows releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    TarGz,
+    TarXz,
+    TarZst,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Every supported format, in the order they are tried when matching file names.
+    pub const ALL: [ArchiveFormat; 4] = [
+        ArchiveFormat::TarGz,
+        ArchiveFormat::TarXz,
+        ArchiveFormat::TarZst,
+        ArchiveFormat::Zip,
+    ];
+
+    /// Returns the file extension of the format, without a leading dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarXz => "tar.xz",
+            ArchiveFormat::TarZst => "tar.zst",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+
+    /// Detects the format from a file name or URL such as `nvim-linux-x86_64.tar.gz`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// assert_eq!(ArchiveFormat::from_file_name("nvim-win64.zip"), Some(ArchiveFormat::Zip));
+    /// ```
+    pub fn from_file_name(name: &str) -> Option<ArchiveFormat> {
+        let name = name.to_ascii_lowercase();
+        let short_extension = |format| match format {
+            ArchiveFormat::TarGz => ".tgz",
+            ArchiveFormat::TarXz => ".txz",
+            ArchiveFormat::TarZst => ".tzst",
+            ArchiveFormat::Zip => ".zip",
+        };
+
+        ArchiveFormat::ALL.into_iter().find(|format| {
+            name.ends_with(&format!(".{}", format.extension()))
+                || name.ends_with(short_extension(*format))
+        })
+    }
+
+    /// Detects the format from the first bytes of an archive.
+    ///
+    /// The compression of tarballs is detected, the tarball inside is assumed.
+    pub fn from_magic(header: &[u8]) -> Option<ArchiveFormat> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(ArchiveFormat::TarGz)
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(ArchiveFormat::TarXz)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(ArchiveFormat::TarZst)
+        } else if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+
+    /// Detects the format of an archive on disk from its magic bytes, falling back to its file name.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be read or its format isn't supported.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let format = ArchiveFormat::detect(Path::new("/path/to/bob/.staging/nightly.tar.gz"))?;
+    /// ```
+    pub fn detect(path: &Path) -> Result<ArchiveFormat> {
+        let mut header = Vec::with_capacity(6);
+        File::open(path)?.take(6).read_to_end(&mut header)?;
+
+        ArchiveFormat::from_magic(&header)
+            .or_else(|| ArchiveFormat::from_file_name(&path.to_string_lossy()))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unsupported archive format, expected one of {}",
+                    ArchiveFormat::ALL.map(ArchiveFormat::extension).join(", ")
+                )
+            })
+    }
+
+    /// Returns whether the format can be expanded from a stream, zip archives need their central directory at the end of the file.
+    pub fn is_streamable(self) -> bool {
+        self != ArchiveFormat::Zip
+    }
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
 /// Starts the process of expanding a downloaded file.
 ///
 /// This function is asynchronous and uses `tokio::task::spawn_blocking` to run the `expand` function in a separate thread.
@@ -50,7 +164,7 @@ pub async fn start(file: &LocalVersion) -> Result<()> {
     })
     .await
     {
-        Ok(_) => (),
+        Ok(result) => result?,
         Err(error) => return Err(anyhow!(error)),
     }
     tokio::fs::remove_file(format!(
@@ -60,13 +174,17 @@ pub async fn start(file: &LocalVersion) -> Result<()> {
     .await?;
     Ok(())
 }
-/// Expands a downloaded file on Windows.
+
+/// Expands a downloaded file.
 ///
-/// This function is specific to Windows due to the use of certain features like `zip::ZipArchive`.
 /// It takes a `LocalVersion` struct which contains information about the downloaded file, such as its name and format.
-/// The function then opens the file and extracts its contents using `zip::ZipArchive`.
+/// The format of the archive is detected from its magic bytes, so `file_format` only has to match the file name on disk.
+/// Tarballs compressed with gzip, xz or zstd and zip archives are supported on every platform.
 /// During the extraction process, a progress bar is displayed to the user.
-/// After extraction, the function removes the original zip file.
+///
+/// The first path component of every entry is stripped. Entries that would end up outside of the output directory,
+/// through `..`, absolute paths or links, are rejected. Symlinks and hardlinks are recreated as links,
+/// and on unix every entry keeps the mode bits recorded in the archive.
 ///
 /// # Arguments
 ///
@@ -82,201 +200,356 @@ pub async fn start(file: &LocalVersion) -> Result<()> {
 ///
 /// This function will return an error if:
 ///
-/// * The downloaded file could not be opened.
-/// * The file could not be extracted.
-/// * The original zip file could not be removed.
+/// * The downloaded file could not be opened, or its format isn't supported.
+/// * The file could not be decompressed, or any entry could not be read or extracted.
+/// * An entry or link target points outside of the output directory.
+/// * The archive contains an entry type other than files, directories and links.
 ///
 /// # Example
 ///
 /// ```rust
 /// let downloaded_file = LocalVersion {
-///     file_name: "nvim-windows",
-///     file_format: "zip",
+///     file_name: "nvim-linux",
+///     file_format: "tar.gz",
 ///     semver: semver::Version::parse("0.5.0").unwrap(),
 ///     path: "/path/to/downloaded/file",
 /// };
 /// expand(downloaded_file);
 /// ```
-#[cfg(target_family = "windows")]
 fn expand(downloaded_file: &LocalVersion) -> Result<()> {
-    use indicatif::{ProgressBar, ProgressStyle};
-    use std::cmp::min;
-    use std::fs::File;
-    use std::io;
-    use std::path::Path;
-    use zip::ZipArchive;
-
-    if fs::metadata(&downloaded_file.file_name).is_ok() {
-        fs::remove_dir_all(&downloaded_file.file_name)?;
+    let root = Path::new(&downloaded_file.path);
+    let output = root.join(&downloaded_file.file_name);
+    if fs::metadata(&output).is_ok() {
+        fs::remove_dir_all(&output)?;
     }
 
-    let file = File::open(format!(
+    let archive = root.join(format!(
         "{}.{}",
         downloaded_file.file_name, downloaded_file.file_format
-    ))?;
-
-    let mut archive = ZipArchive::new(file)?;
-    let totalsize: u64 = archive.len() as u64;
-
-    let pb = ProgressBar::new(totalsize);
-    pb.set_style(
-        ProgressStyle::with_template(
-            "{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len}",
-        )
-        .unwrap()
-        .progress_chars("█  "),
-    );
-    pb.set_message("Expanding archive");
-
-    std::fs::create_dir(downloaded_file.file_name.clone())?;
-
-    let mut downloaded: u64 = 0;
-
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        let file_path = remove_base_parent(&file.mangled_name());
-        let outpath = Path::new(&downloaded_file.file_name).join(file_path);
+    ));
+    let file = match File::open(&archive) {
+        Ok(value) => value,
+        Err(error) => {
+            return Err(anyhow!(
+                "Failed to open file {}.{}, file doesn't exist. additional info: {error}",
+                downloaded_file.file_name,
+                downloaded_file.file_format
+            ));
+        }
+    };
 
-        if file.is_dir() {
-            fs::create_dir_all(outpath)?;
-        } else {
-            if let Some(parent) = outpath.parent() {
-                if !parent.exists() {
-                    fs::create_dir_all(parent)?;
-                }
-            }
-            let mut outfile = fs::File::create(outpath)?;
-            io::copy(&mut file, &mut outfile)?;
+    match ArchiveFormat::detect(&archive)? {
+        ArchiveFormat::Zip => expand_zip(file, &output)?,
+        format => {
+            // Progress follows the compressed bytes consumed, which is known up front unlike the number of entries
+            let pbw = PbWrapper::new(file.metadata()?.len(), "Expanding archive");
+            expand_tar(pbw.wrap_read(file), format, &output)?;
+            pbw.finish(format!(
+                "Finished expanding to {}/{}",
+                downloaded_file.path, downloaded_file.file_name
+            ));
         }
-        let new = min(downloaded + 1, totalsize);
-        downloaded = new;
-        pb.set_position(new);
     }
-    pb.finish_with_message(format!(
-        "Finished unzipping to {}/{}",
-        downloaded_file.path, downloaded_file.file_name
-    ));
 
     Ok(())
 }
 
-/// Expands a downloaded file on Unix systems (macOS and Linux).
+/// Expands a compressed tar stream into `output`.
 ///
-/// This function is specific to Unix systems due to the use of certain features like `os::unix::fs::PermissionsExt`.
-/// It takes a `LocalVersion` struct which contains information about the downloaded file, such as its name and format.
-/// The function then opens the file, decompresses it using `GzDecoder`, and extracts its contents using `tar::Archive`.
-/// During the extraction process, a progress bar is displayed to the user.
-/// After extraction, the function renames the `nvim-osx64` directory to `nvim-macos` if it exists.
-/// Finally, it sets the permissions of the `nvim` binary to `0o551`.
+/// The stream is read exactly once, so it can come straight from a download instead of a file on disk.
+/// The first path component of every entry is stripped, see `expand` for how entries are validated.
 ///
 /// # Arguments
 ///
-/// * `downloaded_file` - A `LocalVersion` struct representing the downloaded file.
+/// * `reader` - The compressed archive.
+/// * `format` - The format of the archive, it must be streamable.
+/// * `output` - A reference to the directory the archive is expanded into, it is created if it doesn't exist.
 ///
 /// # Returns
 ///
 /// This function returns a `Result` that indicates whether the operation was successful.
-/// If the operation was successful, the function returns `Ok(())`.
-/// If the operation failed, the function returns `Err` with a description of the error.
 ///
 /// # Errors
 ///
-/// This function will return an error if:
-///
-/// * The downloaded file could not be opened.
-/// * The file could not be decompressed or extracted.
-/// * The `nvim-osx64` directory could not be renamed.
-/// * The permissions of the `nvim` binary could not be set.
+/// This function will return an error if the format is zip, the stream could not be decompressed, or any entry could not be read, is unsafe or could not be extracted.
 ///
 /// # Example
 ///
 /// ```rust
-/// let downloaded_file = LocalVersion {
-///     file_name: "nvim-linux",
-///     file_format: "tar.gz",
-///     semver: semver::Version::parse("0.5.0").unwrap(),
-///     path: "/path/to/downloaded/file",
-/// };
-/// expand(downloaded_file);
+/// let file = std::fs::File::open("/path/to/bob/.staging/nightly.tar.gz")?;
+/// expand_tar(file, ArchiveFormat::TarGz, Path::new("/path/to/bob/.staging/nightly"))?;
 /// ```
-#[cfg(unix)]
-fn expand(downloaded_file: &LocalVersion) -> Result<()> {
-    use flate2::read::GzDecoder;
-    use indicatif::{ProgressBar, ProgressStyle};
-    use std::cmp::min;
-    use std::fs::File;
-    use std::io;
-    use std::{os::unix::fs::PermissionsExt, path::PathBuf};
-    use tar::Archive;
+pub fn expand_tar<R: Read>(reader: R, format: ArchiveFormat, output: &Path) -> Result<()> {
+    let mut archive = tar::Archive::new(decompress(reader, format)?);
+
+    fs::create_dir_all(output)?;
+
+    // Directory modes are applied last, a read-only directory would otherwise reject its own entries
+    let mut directory_modes = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry.map_err(|error| anyhow!("Failed to read archive entry: {error}"))?;
+        let entry_path = entry.path()?.into_owned();
 
-    if fs::metadata(&downloaded_file.file_name).is_ok() {
-        fs::remove_dir_all(&downloaded_file.file_name)?;
+        if let Some(mode) = extract_tar_entry(&mut entry, output)
+            .map_err(|error| anyhow!("Failed to extract {}: {error}", entry_path.display()))?
+        {
+            directory_modes.push(mode);
+        }
     }
 
-    let file = match File::open(format!(
-        "{}.{}",
-        downloaded_file.file_name, downloaded_file.file_format
-    )) {
-        Ok(value) => value,
-        Err(error) => {
-            return Err(anyhow!(
-                "Failed to open file {}.{}, file doesn't exist. additional info: {error}",
-                downloaded_file.file_name,
-                downloaded_file.file_format
-            ));
+    for (directory, mode) in directory_modes.into_iter().rev() {
+        set_mode(&directory, mode)?;
+    }
+
+    Ok(())
+}
+
+/// Wraps a compressed tar stream in the decoder for its format.
+fn decompress<'a, R: Read + 'a>(reader: R, format: ArchiveFormat) -> Result<Box<dyn Read + 'a>> {
+    Ok(match format {
+        ArchiveFormat::TarGz => Box::new(flate2::read::GzDecoder::new(reader)),
+        ArchiveFormat::TarXz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        ArchiveFormat::TarZst => Box::new(zstd::Decoder::new(reader)?),
+        ArchiveFormat::Zip => return Err(anyhow!("Zip archives can't be expanded from a stream")),
+    })
+}
+
+/// Extracts a single tar entry into `output`.
+///
+/// Returns the directory and its mode if the entry is a directory, so the mode can be applied once everything is extracted.
+fn extract_tar_entry<R: Read>(
+    entry: &mut tar::Entry<R>,
+    output: &Path,
+) -> Result<Option<(PathBuf, u32)>> {
+    use tar::EntryType;
+
+    let entry_type = entry.header().entry_type();
+    if matches!(entry_type, EntryType::XGlobalHeader | EntryType::XHeader) {
+        return Ok(None);
+    }
+
+    let Some(relative) = strip_entry_path(&entry.path()?)? else {
+        // The top level directory of the archive itself
+        return Ok(None);
+    };
+    let destination = output.join(&relative);
+    ensure_no_symlink_ancestors(output, &relative)?;
+
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mode = entry.header().mode()? & 0o7777;
+
+    match entry_type {
+        EntryType::Directory => {
+            fs::create_dir_all(&destination)?;
+            Ok(Some((destination, mode)))
         }
+        EntryType::Regular | EntryType::Continuous => {
+            remove_existing(&destination)?;
+            let mut outfile = File::create(&destination)?;
+            io::copy(entry, &mut outfile)?;
+            set_mode(&destination, mode)?;
+            Ok(None)
+        }
+        EntryType::Symlink => {
+            let target = entry
+                .link_name()?
+                .ok_or_else(|| anyhow!("Symlink without a target"))?
+                .into_owned();
+            create_symlink(&target, &relative, &destination)?;
+            Ok(None)
+        }
+        EntryType::Link => {
+            let target = entry
+                .link_name()?
+                .ok_or_else(|| anyhow!("Hardlink without a target"))?
+                .into_owned();
+            let target = strip_entry_path(&target)?
+                .ok_or_else(|| anyhow!("Hardlink to the archive root"))?;
+            ensure_no_symlink_ancestors(output, &target)?;
+
+            remove_existing(&destination)?;
+            fs::hard_link(output.join(target), &destination)?;
+            Ok(None)
+        }
+        other => Err(anyhow!("Unsupported entry type {other:?}")),
+    }
+}
+
+/// Expands a zip archive into `output`, with the same entry validation as tarballs.
+fn expand_zip(file: File, output: &Path) -> Result<()> {
+    // The central directory lists every entry, so the entry count is exact
+    let mut archive = zip::ZipArchive::new(file)?;
+    let pbw = PbWrapper::with_count(archive.len() as u64, "Expanding archive");
+
+    fs::create_dir_all(output)?;
+
+    let mut directory_modes = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let entry_name = entry.name().to_string();
+
+        if let Some(mode) = extract_zip_entry(&mut entry, output)
+            .map_err(|error| anyhow!("Failed to extract {entry_name}: {error}"))?
+        {
+            directory_modes.push(mode);
+        }
+        pbw.set_position(i as u64 + 1);
+    }
+
+    for (directory, mode) in directory_modes.into_iter().rev() {
+        set_mode(&directory, mode)?;
+    }
+
+    pbw.finish(format!("Finished unzipping to {}", output.display()));
+    Ok(())
+}
+
+/// Extracts a single zip entry into `output`.
+///
+/// Returns the directory and its mode if the entry is a directory that recorded one, so the mode can be applied once everything is extracted.
+fn extract_zip_entry(
+    entry: &mut zip::read::ZipFile<'_>,
+    output: &Path,
+) -> Result<Option<(PathBuf, u32)>> {
+    let Some(relative) = strip_entry_path(Path::new(entry.name()))? else {
+        return Ok(None);
     };
-    let decompress_stream = GzDecoder::new(file);
-    let mut archive = Archive::new(decompress_stream);
-
-    let totalsize = 1692; // hard coding this is pretty unwise, but you cant get the length of an archive in tar-rs unlike zip-rs
-    let pb = ProgressBar::new(totalsize);
-    pb.set_style(
-        ProgressStyle::with_template(
-            "{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len}",
+    let destination = output.join(&relative);
+    ensure_no_symlink_ancestors(output, &relative)?;
+
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mode = entry.unix_mode().map(|mode| mode & 0o7777);
+
+    if entry.is_dir() {
+        fs::create_dir_all(&destination)?;
+        return Ok(mode.map(|mode| (destination, mode)));
+    }
+
+    if entry.is_symlink() {
+        let mut target = String::new();
+        entry.read_to_string(&mut target)?;
+        create_symlink(Path::new(&target), &relative, &destination)?;
+        return Ok(None);
+    }
+
+    remove_existing(&destination)?;
+    let mut outfile = File::create(&destination)?;
+    io::copy(entry, &mut outfile)?;
+    if let Some(mode) = mode {
+        set_mode(&destination, mode)?;
+    }
+    Ok(None)
+}
+
+/// Strips the first component of an entry path and validates the rest.
+///
+/// Returns `None` for the top level directory itself, and an error if the path is absolute or contains `..`.
+fn strip_entry_path(path: &Path) -> Result<Option<PathBuf>> {
+    use std::path::Component;
+
+    let escapes = path.components().any(|component| {
+        matches!(
+            component,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
         )
-        .unwrap()
-        .progress_chars("█  "),
-    );
-    pb.set_message("Expanding archive");
-
-    let mut downloaded: u64 = 0;
-    for file in archive.entries()? {
-        match file {
-            Ok(mut file) => {
-                let mut outpath = PathBuf::new();
-                outpath.push(&downloaded_file.file_name);
-                let no_parent_file = remove_base_parent(&file.path().unwrap());
-                outpath.push(no_parent_file);
-
-                let file_name = format!("{}", file.path()?.display()); // file.path()?.is_dir() always returns false... weird
-                if file_name.ends_with('/') {
-                    fs::create_dir_all(outpath)?;
-                } else {
-                    if let Some(parent) = outpath.parent() {
-                        if !parent.exists() {
-                            fs::create_dir_all(parent)?;
-                        }
-                    }
-                    let mut outfile = fs::File::create(outpath)?;
-                    io::copy(&mut file, &mut outfile)?;
+    });
+    if escapes {
+        return Err(anyhow!(
+            "Entry path {} points outside of the archive",
+            path.display()
+        ));
+    }
+
+    let relative: PathBuf = remove_base_parent(path)
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+    Ok((!relative.as_os_str().is_empty()).then_some(relative))
+}
+
+/// Lexically resolves `.` and `..` in a relative path, returning `None` if it climbs above its root.
+fn normalize_within(path: &Path) -> Option<PathBuf> {
+    use std::path::Component;
+
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
                 }
-                let new = min(downloaded + 1, totalsize);
-                downloaded = new;
-                pb.set_position(new);
             }
-            Err(error) => println!("{error}"),
+            Component::RootDir | Component::Prefix(_) => return None,
         }
     }
-    pb.finish_with_message(format!(
-        "Finished expanding to {}/{}",
-        downloaded_file.path, downloaded_file.file_name
-    ));
+    Some(normalized)
+}
 
-    let file = &format!("{}/bin/nvim", downloaded_file.file_name);
-    let mut perms = fs::metadata(file)?.permissions();
-    perms.set_mode(0o551);
-    fs::set_permissions(file, perms)?;
+/// Makes sure no directory between `output` and `relative` is a symlink, so entries can't be written through one.
+fn ensure_no_symlink_ancestors(output: &Path, relative: &Path) -> Result<()> {
+    let mut current = output.to_path_buf();
+    if let Some(parent) = relative.parent() {
+        for component in parent.components() {
+            current.push(component);
+            if fs::symlink_metadata(&current).is_ok_and(|metadata| metadata.is_symlink()) {
+                return Err(anyhow!(
+                    "Entry {} would be written through the symlink {}",
+                    relative.display(),
+                    current.display()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes a file or link that is about to be replaced by a later entry with the same path.
+fn remove_existing(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => Err(anyhow!("A directory already exists here")),
+        Ok(_) => Ok(fs::remove_file(path)?),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Sets the permission bits of a path, modes only exist on unix.
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+/// Sets the permission bits of a path, modes only exist on unix.
+#[cfg(windows)]
+fn set_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Creates the symlink `destination` pointing to `target`, after making sure the target stays inside the archive.
+///
+/// `relative` is the path of the link inside the archive, relative targets are resolved from its parent.
+fn create_symlink(target: &Path, relative: &Path, destination: &Path) -> Result<()> {
+    let link_parent = relative.parent().unwrap_or(Path::new(""));
+    if target.is_absolute() || normalize_within(&link_parent.join(target)).is_none() {
+        return Err(anyhow!(
+            "Symlink target {} points outside of the archive",
+            target.display()
+        ));
+    }
+
+    remove_existing(destination)?;
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, destination)?;
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_file(target, destination)?;
     Ok(())
 }
 
@@ -317,3 +590,212 @@ fn remove_base_parent(path: &Path) -> PathBuf {
 
     components.as_path().to_path_buf()
 }
+
+#[cfg(all(test, unix))]
+mod unarchive_tests {
+    use super::*;
+    use flate2::{Compression, write::GzEncoder};
+    use std::os::unix::fs::PermissionsExt;
+    use tar::{Builder, EntryType, Header};
+    use tempfile::TempDir;
+
+    enum Entry<'a> {
+        Dir(&'a str, u32),
+        File(&'a str, u32, &'a [u8]),
+        Symlink(&'a str, &'a str),
+        Hardlink(&'a str, &'a str),
+    }
+
+    fn header(name: &str, entry_type: EntryType, mode: u32, size: u64) -> Header {
+        let mut header = Header::new_gnu();
+        // Written raw, `Header::set_path` refuses the malicious paths under test
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_size(size);
+        header.set_cksum();
+        header
+    }
+
+    fn expand_archive(entries: &[Entry]) -> (TempDir, Result<()>) {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+
+        let encoder = GzEncoder::new(
+            fs::File::create(root.join("archive.tar.gz")).unwrap(),
+            Compression::fast(),
+        );
+        let mut builder = Builder::new(encoder);
+        for entry in entries {
+            match entry {
+                Entry::Dir(path, mode) => builder
+                    .append(&header(path, EntryType::Directory, *mode, 0), &[][..])
+                    .unwrap(),
+                Entry::File(path, mode, data) => builder
+                    .append(
+                        &header(path, EntryType::Regular, *mode, data.len() as u64),
+                        *data,
+                    )
+                    .unwrap(),
+                Entry::Symlink(path, target) | Entry::Hardlink(path, target) => {
+                    let entry_type = match entry {
+                        Entry::Symlink(..) => EntryType::Symlink,
+                        _ => EntryType::Link,
+                    };
+                    let mut header = header(path, entry_type, 0o777, 0);
+                    header.as_old_mut().linkname[..target.len()].copy_from_slice(target.as_bytes());
+                    header.set_cksum();
+                    builder.append(&header, &[][..]).unwrap();
+                }
+            }
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let result = expand(&LocalVersion {
+            file_name: "archive".to_string(),
+            file_format: "tar.gz".to_string(),
+            path: root.display().to_string(),
+            semver: None,
+        });
+        (temp, result)
+    }
+
+    #[test]
+    fn links_and_modes_are_preserved() {
+        let (temp, result) = expand_archive(&[
+            Entry::Dir("nvim-linux-x86_64/", 0o755),
+            Entry::Dir("nvim-linux-x86_64/bin/", 0o755),
+            Entry::File("nvim-linux-x86_64/bin/nvim", 0o755, b"binary"),
+            Entry::File("nvim-linux-x86_64/lib/libfoo.so.1", 0o644, b"library"),
+            Entry::Symlink("nvim-linux-x86_64/lib/libfoo.so", "libfoo.so.1"),
+            Entry::Hardlink("nvim-linux-x86_64/bin/vi", "nvim-linux-x86_64/bin/nvim"),
+        ]);
+        result.unwrap();
+
+        let output = temp.path().join("archive");
+        let mode = |path: &str| {
+            fs::metadata(output.join(path))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o777
+        };
+        assert_eq!(mode("bin/nvim"), 0o755);
+        assert_eq!(mode("lib/libfoo.so.1"), 0o644);
+        assert_eq!(
+            fs::read_link(output.join("lib/libfoo.so")).unwrap(),
+            PathBuf::from("libfoo.so.1")
+        );
+        assert_eq!(fs::read(output.join("bin/vi")).unwrap(), b"binary");
+    }
+
+    #[test]
+    fn entries_escaping_the_output_are_rejected() {
+        let cases: [&[Entry]; 4] = [
+            &[Entry::File("nvim-linux-x86_64/../../evil", 0o644, b"evil")],
+            &[Entry::File("/tmp/evil", 0o644, b"evil")],
+            &[Entry::Symlink("nvim-linux-x86_64/share", "../../..")],
+            &[
+                Entry::Symlink("nvim-linux-x86_64/share", "/tmp"),
+                Entry::File("nvim-linux-x86_64/share/evil", 0o644, b"evil"),
+            ],
+        ];
+
+        for (index, entries) in cases.iter().enumerate() {
+            let (temp, result) = expand_archive(entries);
+            assert!(result.is_err(), "case {index} was extracted");
+            assert!(!temp.path().join("evil").exists());
+        }
+    }
+
+    #[test]
+    fn formats_are_detected_from_names_and_magic_bytes() {
+        for (name, format) in [
+            ("nvim-linux-x86_64.tar.gz", Some(ArchiveFormat::TarGz)),
+            ("https://example.com/nvim.TGZ", Some(ArchiveFormat::TarGz)),
+            ("nvim.tar.xz", Some(ArchiveFormat::TarXz)),
+            ("nvim.tar.zst", Some(ArchiveFormat::TarZst)),
+            ("nvim-win64.zip", Some(ArchiveFormat::Zip)),
+            ("nvim.appimage", None),
+        ] {
+            assert_eq!(ArchiveFormat::from_file_name(name), format, "{name}");
+        }
+
+        assert_eq!(
+            ArchiveFormat::from_magic(&[0x1f, 0x8b, 0x08]),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic(b"\xfd7zXZ\x00"),
+            Some(ArchiveFormat::TarXz)
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic(&[0x28, 0xb5, 0x2f, 0xfd]),
+            Some(ArchiveFormat::TarZst)
+        );
+        assert_eq!(
+            ArchiveFormat::from_magic(b"PK\x03\x04"),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(ArchiveFormat::from_magic(b"#!/bin/sh"), None);
+    }
+
+    #[test]
+    fn every_format_is_expanded() {
+        use std::io::Write;
+
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+
+        let mut builder = Builder::new(Vec::new());
+        let mut header = Header::new_gnu();
+        header.set_size(6);
+        header.set_mode(0o755);
+        builder
+            .append_data(&mut header, "nvim-linux-x86_64/bin/nvim", &b"binary"[..])
+            .unwrap();
+        let tarball = builder.into_inner().unwrap();
+
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        zip.start_file(
+            "nvim-win64/bin/nvim",
+            zip::write::SimpleFileOptions::default().unix_permissions(0o755),
+        )
+        .unwrap();
+        zip.write_all(b"binary").unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::fast());
+        gzip.write_all(&tarball).unwrap();
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 1);
+        xz.write_all(&tarball).unwrap();
+
+        for (format, content) in [
+            (ArchiveFormat::TarGz, gzip.finish().unwrap()),
+            (ArchiveFormat::TarXz, xz.finish().unwrap()),
+            (
+                ArchiveFormat::TarZst,
+                zstd::encode_all(tarball.as_slice(), 1).unwrap(),
+            ),
+            (ArchiveFormat::Zip, zip.clone()),
+        ] {
+            let file_name = format!("{format:?}");
+            fs::write(root.join(format!("{file_name}.{format}")), content).unwrap();
+            expand(&LocalVersion {
+                file_name: file_name.clone(),
+                file_format: format.to_string(),
+                path: root.display().to_string(),
+                semver: None,
+            })
+            .unwrap();
+
+            let nvim = root.join(&file_name).join("bin").join("nvim");
+            assert_eq!(fs::read(&nvim).unwrap(), b"binary", "{format}");
+            assert_eq!(
+                fs::metadata(&nvim).unwrap().permissions().mode() & 0o777,
+                0o755,
+                "{format}"
+            );
+        }
+    }
+}
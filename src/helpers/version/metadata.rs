@@ -0,0 +1,224 @@
+use anyhow::Result;
+use std::path::Path;
+use tokio::fs;
+
+use crate::config::Config;
+use crate::helpers::version::types::InstallMetadata;
+
+use super::get_installed_releases;
+
+/// Name of the metadata file stored inside every installed version's directory.
+pub const METADATA_FILE: &str = "bob-metadata.json";
+
+/// Writes the install metadata of a version into its directory.
+///
+/// # Arguments
+///
+/// * `version_dir` - The directory the version is installed in.
+/// * `metadata` - The metadata to record.
+///
+/// # Returns
+///
+/// * `Result<()>` - Returns a `Result` that indicates whether the operation was successful or not.
+///
+/// # Errors
+///
+/// This function will return an error if the metadata cannot be serialized or written.
+///
+/// # Example
+///
+/// ```rust
+/// let metadata = InstallMetadata { tag_name: "v0.10.0".to_string(), stable: true, installed_at: Utc::now(), release: None };
+/// write_metadata(Path::new("/path/to/bob/v0.10.0"), &metadata).await?;
+/// ```
+pub async fn write_metadata(version_dir: &Path, metadata: &InstallMetadata) -> Result<()> {
+    let json = serde_json::to_string_pretty(metadata)?;
+    fs::write(version_dir.join(METADATA_FILE), json).await?;
+
+    Ok(())
+}
+
+/// Reads the install metadata of a version from its directory.
+///
+/// # Arguments
+///
+/// * `version_dir` - The directory the version is installed in.
+///
+/// # Returns
+///
+/// * `Result<Option<InstallMetadata>>` - Returns the metadata, or `None` for versions installed before bob recorded metadata.
+///
+/// # Errors
+///
+/// This function will return an error if the metadata file exists but cannot be parsed.
+///
+/// # Example
+///
+/// ```rust
+/// if let Some(metadata) = read_metadata(Path::new("/path/to/bob/v0.10.0")).await? {
+///     println!("Installed at {}", metadata.installed_at);
+/// }
+/// ```
+pub async fn read_metadata(version_dir: &Path) -> Result<Option<InstallMetadata>> {
+    match fs::read_to_string(version_dir.join(METADATA_FILE)).await {
+        Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Retrieves the stable release recorded locally, without accessing the network.
+///
+/// This function returns the tag name of the most recently installed version that was installed as `stable`. Versions installed before bob recorded metadata are not marked, so when no stable install is recorded the newest installed release is used instead.
+///
+/// # Arguments
+///
+/// * `config` - The configuration to retrieve the downloads directory from.
+///
+/// # Returns
+///
+/// * `Result<Option<String>>` - Returns the tag name of the stable release, or `None` if no release is installed.
+///
+/// # Errors
+///
+/// This function will return an error if the downloads directory cannot be read or a metadata file cannot be parsed.
+///
+/// # Example
+///
+/// ```rust
+/// let config = Config::default();
+/// if let Some(stable) = get_recorded_stable(&config).await? {
+///     println!("Stable is {stable}");
+/// }
+/// ```
+pub async fn get_recorded_stable(config: &Config) -> Result<Option<String>> {
+    let downloads_dir = crate::helpers::directories::get_downloads_directory(config).await?;
+    let installed = get_installed_releases(config).await?;
+
+    let mut recorded: Option<InstallMetadata> = None;
+    for (_, tag_name) in &installed {
+        let Some(metadata) = read_metadata(&downloads_dir.join(tag_name)).await? else {
+            continue;
+        };
+
+        if metadata.stable
+            && recorded
+                .as_ref()
+                .is_none_or(|current| metadata.installed_at > current.installed_at)
+        {
+            recorded = Some(metadata);
+        }
+    }
+
+    if let Some(metadata) = recorded {
+        return Ok(Some(metadata.tag_name));
+    }
+
+    Ok(installed
+        .into_iter()
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, tag_name)| tag_name))
+}
+
+#[cfg(test)]
+mod metadata_tests {
+    use super::*;
+    use crate::github_requests::UpstreamVersion;
+    use crate::helpers::version::types::{AppliedPatch, InstallSource};
+    use chrono::{Duration, Utc};
+
+    #[tokio::test]
+    async fn recorded_stable_prefers_latest_stable_install() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        for tag_name in ["v0.9.5", "v0.10.0", "v0.11.0", "nightly"] {
+            fs::create_dir_all(root.join(tag_name)).await.unwrap();
+        }
+        let config = Config {
+            downloads_location: Some(root.to_string_lossy().to_string()),
+            ..Config::default()
+        };
+
+        // Without metadata the newest installed release is used
+        assert_eq!(
+            get_recorded_stable(&config).await.unwrap().as_deref(),
+            Some("v0.11.0")
+        );
+
+        let now = Utc::now();
+        for (tag_name, stable, installed_at) in [
+            ("v0.9.5", true, now - Duration::days(30)),
+            ("v0.10.0", true, now),
+            ("v0.11.0", false, now),
+        ] {
+            let metadata = InstallMetadata {
+                tag_name: tag_name.to_string(),
+                stable,
+                installed_at,
+                release: None,
+                source: None,
+            };
+            write_metadata(&root.join(tag_name), &metadata)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            get_recorded_stable(&config).await.unwrap().as_deref(),
+            Some("v0.10.0")
+        );
+    }
+
+    #[tokio::test]
+    async fn stable_release_round_trips() {
+        let temp = tempfile::tempdir().unwrap();
+        let published_at = Utc::now() - Duration::days(3);
+        let metadata = InstallMetadata {
+            tag_name: "v0.10.4".to_string(),
+            stable: true,
+            installed_at: Utc::now(),
+            release: Some(UpstreamVersion {
+                tag_name: "v0.10.4".to_string(),
+                target_commitish: Some("release-0.10".to_string()),
+                published_at,
+                assets: Vec::new(),
+            }),
+            source: None,
+        };
+        write_metadata(temp.path(), &metadata).await.unwrap();
+
+        let read = read_metadata(temp.path()).await.unwrap().unwrap();
+        assert_eq!(read.tag_name, "v0.10.4");
+        assert!(read.stable);
+        let release = read.release.unwrap();
+        assert_eq!(release.tag_name, "v0.10.4");
+        assert_eq!(release.target_commitish.as_deref(), Some("release-0.10"));
+        assert_eq!(release.published_at, published_at);
+    }
+
+    #[test]
+    fn build_sources_record_their_patches() {
+        // Builds recorded before remotes and patches were tracked still load
+        let source: InstallSource =
+            serde_json::from_str(r#"{"type":"build","git_ref":"v0.10.0"}"#).unwrap();
+        assert_eq!(source.to_string(), "source build of v0.10.0");
+
+        let source = InstallSource::Build {
+            remote: Some("https://github.com/neovim/neovim.git".to_string()),
+            git_ref: "nightly".to_string(),
+            patches: vec![AppliedPatch {
+                path: "/patches/fix.diff".to_string(),
+                sha256: "ab12".to_string(),
+            }],
+            profile: Some("asan".to_string()),
+        };
+        let json = serde_json::to_string(&source).unwrap();
+        assert_eq!(
+            serde_json::from_str::<InstallSource>(&json).unwrap(),
+            source
+        );
+        assert_eq!(
+            source.to_string(),
+            "nightly from https://github.com/neovim/neovim.git with 1 patch (asan profile)"
+        );
+    }
+}
@@ -1,8 +1,9 @@
+pub mod metadata;
 pub mod nightly;
 pub mod types;
 
-use self::types::{ParsedVersion, VersionType};
-use crate::github_requests::get_upstream_stable;
+use self::types::{GitSource, ParsedVersion, VersionType};
+use crate::github_requests::{ensure_online, get_upstream_stable, get_upstream_tags, is_offline};
 use crate::helpers::directories;
 use crate::{
     config::Config,
@@ -10,22 +11,31 @@ use crate::{
 };
 use anyhow::{Context, Result, anyhow};
 use reqwest::Client;
-use semver::Version;
+use semver::{Version, VersionReq};
 use std::path::{Path, PathBuf};
 use tokio::{
     fs::{self, File},
     io::AsyncWriteExt,
 };
-use tracing::info;
+use tracing::{info, warn};
 
 /// Parses the version type from a version string.
 ///
 /// This function takes a version string and determines the type of the version. It supports the following version types: `Nightly`, `Latest`, `Hash`, `Normal`, and `NightlyRollback`.
 ///
+/// User-defined aliases from `Config::aliases` are resolved first, so an alias can stand in for any of the version strings below.
+///
+/// Git sources (`github:<owner>/<repo>@<ref>`, `pr:<number>` and `git+<url>@<ref>`) are returned as `Git` versions named after the source, see `parse_git_source`.
+///
+/// The relative specifiers `stable~N` (the release N releases before the latest stable) and `previous` (the version used before the current one) are resolved to the version they point at.
+///
+/// Partial versions and semver requirements (`0.10`, `~0.9`, `^0.10`, `>=0.9.5, <0.10`) are resolved to the newest matching installed release, or to the newest matching upstream tag when none is installed, and are returned as `Normal` versions.
+///
 /// # Arguments
 ///
 /// * `client` - The client to use for fetching the latest version or commit.
 /// * `version` - The version string to parse.
+/// * `config` - The configuration to retrieve the downloads directory from.
 ///
 /// # Returns
 ///
@@ -37,16 +47,25 @@ use tracing::info;
 ///
 /// * The version string is not valid.
 /// * The latest version or commit cannot be fetched.
+/// * No installed or upstream release matches a version requirement.
+/// * A relative specifier points past the oldest release, or there is no previously used version.
 ///
 /// # Example
 ///
 /// ```rust
 /// let client = Client::new();
+/// let config = Config::default();
 /// let version = "nightly";
-/// let parsed_version = parse_version_type(&client, version).await.unwrap();
+/// let parsed_version = parse_version_type(&client, version, &config).await.unwrap();
 /// println!("The parsed version is {:?}", parsed_version);
 /// ```
-pub async fn parse_version_type(client: &Client, version: &str) -> Result<ParsedVersion> {
+pub async fn parse_version_type(
+    client: &Client,
+    version: &str,
+    config: &Config,
+) -> Result<ParsedVersion> {
+    let version = resolve_alias(version, config);
+
     match version {
         "nightly" => Ok(ParsedVersion {
             tag_name: version.to_string(),
@@ -55,16 +74,20 @@ pub async fn parse_version_type(client: &Client, version: &str) -> Result<Parsed
             semver: None,
         }),
         "stable" | "latest" => {
-            info!("Fetching latest version");
-            let stable_version = get_upstream_stable(client).await?;
-            let cloned_version = stable_version.tag_name.clone();
+            let tag_name = get_stable_tag(client, config).await?;
+            let cloned_version = tag_name.clone();
             Ok(ParsedVersion {
-                tag_name: stable_version.tag_name,
+                tag_name,
                 version_type: VersionType::Latest,
                 non_parsed_string: version.to_string(),
                 semver: Some(Version::parse(&cloned_version.replace('v', ""))?),
             })
         }
+        "previous" => {
+            let previous_version = get_previous_version(config).await?;
+            info!("Resolved previous to {}", previous_version.trim());
+            Box::pin(parse_version_type(client, previous_version.trim(), config)).await
+        }
         "head" | "git" | "HEAD" => {
             info!("Fetching latest commit");
             let latest_commit = get_latest_commit(client).await?;
@@ -76,6 +99,56 @@ pub async fn parse_version_type(client: &Client, version: &str) -> Result<Parsed
             })
         }
         _ => {
+            if let Some(source) = parse_git_source(version, config) {
+                let source = source?;
+                return Ok(ParsedVersion {
+                    tag_name: source.name,
+                    version_type: VersionType::Git,
+                    non_parsed_string: version.to_string(),
+                    semver: None,
+                });
+            }
+
+            if let Some(parsed) = parse_local_install(version, config).await? {
+                return Ok(parsed);
+            }
+
+            if let Some(steps) = parse_stable_offset(version) {
+                info!("Fetching latest version");
+                let stable_version = get_upstream_stable(client).await?;
+                let tags = get_upstream_tags(client).await?;
+                let tag_name = release_before(
+                    tags.into_iter().map(|tag| tag.name),
+                    &stable_version.tag_name,
+                    steps,
+                )
+                .ok_or_else(|| {
+                    anyhow!(
+                        "There is no release {steps} releases before {}",
+                        stable_version.tag_name
+                    )
+                })?;
+                info!("Resolved {version} to {tag_name}");
+                return Ok(ParsedVersion {
+                    semver: Some(Version::parse(&tag_name.replace('v', ""))?),
+                    tag_name,
+                    version_type: VersionType::Normal,
+                    non_parsed_string: version.to_string(),
+                });
+            }
+
+            if let Some(requirement) = parse_version_req(version) {
+                let tag_name = resolve_version_req(client, &requirement, config).await?;
+                let semver = Version::parse(&tag_name.replace('v', ""))?;
+                info!("Resolved {version} to {tag_name}");
+                return Ok(ParsedVersion {
+                    tag_name,
+                    version_type: VersionType::Normal,
+                    non_parsed_string: version.to_string(),
+                    semver: Some(semver),
+                });
+            }
+
             if crate::VERSION_REGEX.is_match(version) {
                 let mut returned_version = version.to_string();
                 if !version.contains('v') {
@@ -91,7 +164,7 @@ pub async fn parse_version_type(client: &Client, version: &str) -> Result<Parsed
                             .context("Unable to parse version string in parse_version_type")?,
                     ),
                 });
-            } else if crate::HASH_REGEX.is_match(version) {
+            } else if is_hash(version) {
                 return Ok(ParsedVersion {
                     tag_name: version.to_string().chars().take(7).collect(),
                     version_type: VersionType::Hash,
@@ -113,13 +186,494 @@ pub async fn parse_version_type(client: &Client, version: &str) -> Result<Parsed
                 "Please provide a proper version string. Valid options are:
 
                     • stable|latest|nightly - Latest stable, most recent, or nightly build
+                    • stable~N|previous     - N releases before stable, or the previously used version
                     • [v]x.x.x              - Specific version (e.g., 0.6.0 or v0.6.0)
-                    • <commit-hash>         - Specific commit hash"
+                    • x.x|~x.x|>=x.x.x      - Newest release matching a partial version or semver range
+                    • <name>                - A version installed with `bob install --as <name>`
+                    • <commit-hash>         - Specific commit hash
+                    • github:<owner>/<repo>[@<ref>], pr:<number>, git+<url>[@<ref>]
+                                            - A branch, pull request or ref built from source"
             ))
         }
     }
 }
 
+/// Retrieves the tag name of the latest stable release.
+///
+/// In offline mode the stable release recorded at install time is used. Otherwise the latest release is fetched from GitHub, falling back to the recorded one when the request fails, so installed versions keep working without network access.
+///
+/// # Arguments
+///
+/// * `client` - The client to use for fetching the latest release.
+/// * `config` - The configuration to retrieve the downloads directory from.
+///
+/// # Returns
+///
+/// * `Result<String>` - Returns the tag name of the stable release.
+///
+/// # Errors
+///
+/// This function will return an error if the latest release cannot be fetched and no stable release is recorded locally.
+///
+/// # Example
+///
+/// ```rust
+/// let client = Client::new();
+/// let config = Config::default();
+/// let stable = get_stable_tag(&client, &config).await?;
+/// ```
+async fn get_stable_tag(client: &Client, config: &Config) -> Result<String> {
+    if is_offline() {
+        return metadata::get_recorded_stable(config).await?.ok_or_else(|| {
+            anyhow!("No stable release is installed, run `bob install stable` while online first")
+        });
+    }
+
+    info!("Fetching latest version");
+    match get_upstream_stable(client).await {
+        Ok(stable_version) => Ok(stable_version.tag_name),
+        Err(error) => {
+            let Some(recorded) = metadata::get_recorded_stable(config).await? else {
+                return Err(error);
+            };
+            warn!("Failed to fetch latest version ({error}), using installed stable {recorded}");
+            Ok(recorded)
+        }
+    }
+}
+
+/// Checks whether a string is a version specifier understood by `parse_version_type`, without resolving it.
+///
+/// # Arguments
+///
+/// * `version` - The string to check.
+///
+/// # Returns
+///
+/// * `bool` - Returns `true` if the string is a keyword, a version, a version requirement, a commit hash or a nightly rollback.
+///
+/// # Example
+///
+/// ```rust
+/// assert!(is_version_specifier("stable~1"));
+/// assert!(!is_version_specifier("work"));
+/// ```
+pub fn is_version_specifier(version: &str) -> bool {
+    matches!(
+        version,
+        "nightly" | "stable" | "latest" | "head" | "git" | "HEAD" | "previous"
+    ) || parse_stable_offset(version).is_some()
+        || parse_version_req(version).is_some()
+        || crate::VERSION_REGEX.is_match(version)
+        || is_hash(version)
+        || crate::NIGHTLY_REGEX.is_match(version)
+}
+
+/// Checks whether a string is a commit hash as a whole.
+///
+/// `HASH_REGEX` also matches a hex word inside a longer name, so names such as `pr-12345` or `nightly-abcdef1` aren't hashes.
+///
+/// # Arguments
+///
+/// * `version` - The string to check.
+///
+/// # Returns
+///
+/// * `bool` - Returns `true` if the whole string is a 5 to 40 character hex hash.
+///
+/// # Example
+///
+/// ```rust
+/// assert!(is_hash("abc123"));
+/// assert!(!is_hash("pr-12345"));
+/// ```
+pub fn is_hash(version: &str) -> bool {
+    crate::HASH_REGEX
+        .find(version)
+        .is_some_and(|hash| hash.as_str() == version)
+}
+
+/// Checks whether a name can be used for a version installed under a custom name.
+///
+/// Custom names must start with a letter or digit, may only contain letters, digits, `.`, `_` and `-`,
+/// and must not be a version specifier or the name of one of bob's own directories.
+///
+/// # Arguments
+///
+/// * `name` - The name to check.
+///
+/// # Returns
+///
+/// * `bool` - Returns `true` if `name` can be used as a custom name.
+///
+/// # Example
+///
+/// ```rust
+/// assert!(is_valid_install_name("ci-build"));
+/// assert!(!is_valid_install_name("nightly"));
+/// ```
+pub fn is_valid_install_name(name: &str) -> bool {
+    let is_well_formed = name.chars().next().is_some_and(char::is_alphanumeric)
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+
+    is_well_formed
+        && !matches!(name, "nvim-bin" | "neovim-git" | "platforms")
+        && !is_version_specifier(name)
+}
+
+/// The repository Neovim is built from when `Config::git_remote` isn't set.
+const NEOVIM_GIT_REMOTE: &str = "https://github.com/neovim/neovim.git";
+
+/// Returns the repository Neovim is built from, `Config::git_remote` or the official repository.
+///
+/// # Example
+///
+/// ```rust
+/// let remote = git_remote(&config);
+/// ```
+pub fn git_remote(config: &Config) -> &str {
+    config.git_remote.as_deref().unwrap_or(NEOVIM_GIT_REMOTE)
+}
+
+/// Parses a git source to build Neovim from.
+///
+/// The following sources are supported, without a ref the default branch of the repository is built:
+/// * `github:<owner>/<repo>[@<ref>]` - A repository on GitHub, for example a fork.
+/// * `pr:<number>` - A pull request of the repository from `git_remote`.
+/// * `git+<url>[@<ref>]` - Any repository git can fetch from, for example `git+https://git.example.com/neovim.git@fixes`.
+///
+/// The build is named after the source, for example `user-neovim-my-branch`, `pr-12345` or `neovim-fixes`.
+///
+/// # Arguments
+///
+/// * `version` - The version string to parse.
+/// * `config` - The configuration to retrieve the default remote from.
+///
+/// # Returns
+///
+/// * `Option<Result<GitSource>>` - Returns `None` if `version` isn't a git source, or the parsed source.
+///
+/// # Errors
+///
+/// The inner result is an error if the source is malformed or no valid name can be derived from it.
+///
+/// # Example
+///
+/// ```rust
+/// let source = parse_git_source("pr:12345", &config).unwrap()?;
+/// assert_eq!(source.git_ref, "refs/pull/12345/head");
+/// ```
+pub fn parse_git_source(version: &str, config: &Config) -> Option<Result<GitSource>> {
+    let (remote, git_ref, name) = if let Some(repository) = version.strip_prefix("github:") {
+        let (repository, git_ref) = split_git_ref(repository);
+        let Some((owner, repo)) = repository
+            .split_once('/')
+            .filter(|(owner, repo)| !owner.is_empty() && !repo.is_empty() && !repo.contains('/'))
+        else {
+            return Some(Err(anyhow!(
+                "{version} is not a GitHub repository, expected github:<owner>/<repo>[@<ref>]"
+            )));
+        };
+        let repo = repo.trim_end_matches(".git");
+        (
+            format!("https://github.com/{owner}/{repo}.git"),
+            git_ref,
+            format!("{owner}-{repo}"),
+        )
+    } else if let Some(number) = version.strip_prefix("pr:") {
+        if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+            return Some(Err(anyhow!(
+                "{version} is not a pull request, expected pr:<number>"
+            )));
+        }
+        return Some(Ok(GitSource {
+            remote: git_remote(config).to_string(),
+            git_ref: format!("refs/pull/{number}/head"),
+            name: format!("pr-{number}"),
+        }));
+    } else if let Some(url) = version.strip_prefix("git+") {
+        let (url, git_ref) = split_git_ref(url);
+        let repo = url
+            .trim_end_matches('/')
+            .rsplit(['/', ':'])
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches(".git");
+        if repo.is_empty() {
+            return Some(Err(anyhow!(
+                "{version} is not a git repository, expected git+<url>[@<ref>]"
+            )));
+        }
+        (url.to_string(), git_ref, repo.to_string())
+    } else {
+        return None;
+    };
+
+    let name: String = match git_ref {
+        Some(git_ref) => format!("{name}-{git_ref}"),
+        None => name,
+    }
+    .chars()
+    .map(|c| {
+        if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+            c
+        } else {
+            '-'
+        }
+    })
+    .collect();
+
+    if !is_valid_install_name(&name) {
+        return Some(Err(anyhow!(
+            "Can't install {version} as {name}, it isn't a valid name for a build"
+        )));
+    }
+
+    Some(Ok(GitSource {
+        remote,
+        git_ref: git_ref.unwrap_or("HEAD").to_string(),
+        name,
+    }))
+}
+
+/// Splits the ref off a repository, `<repository>@<ref>`.
+///
+/// Only an `@` after the first `/` of the path separates a ref, so `ssh://git@host/neovim.git` keeps its user.
+fn split_git_ref(repository: &str) -> (&str, Option<&str>) {
+    let path_start = repository.find("://").map_or(0, |index| index + 3);
+    match repository.rfind('@') {
+        Some(at) if at > path_start && repository[path_start..at].contains('/') => {
+            let git_ref = &repository[at + 1..];
+            (&repository[..at], (!git_ref.is_empty()).then_some(git_ref))
+        }
+        _ => (repository, None),
+    }
+}
+
+/// Parses a version installed under a custom name, such as one installed with `bob install --from-file <path> --as <name>`.
+///
+/// A name is only treated as a custom install if its directory exists and its metadata records where it came from.
+///
+/// # Arguments
+///
+/// * `version` - The version string to parse.
+/// * `config` - The configuration to retrieve the downloads directory from.
+///
+/// # Returns
+///
+/// * `Result<Option<ParsedVersion>>` - Returns a `Local` version, or `None` if `version` isn't a custom install.
+async fn parse_local_install(version: &str, config: &Config) -> Result<Option<ParsedVersion>> {
+    if !is_valid_install_name(version) {
+        return Ok(None);
+    }
+
+    let downloads_dir = directories::get_downloads_directory(config).await?;
+    let Some(metadata) = metadata::read_metadata(&downloads_dir.join(version)).await? else {
+        return Ok(None);
+    };
+
+    if metadata.source.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(ParsedVersion {
+        tag_name: version.to_string(),
+        version_type: VersionType::Local,
+        non_parsed_string: version.to_string(),
+        semver: None,
+    }))
+}
+
+/// Resolves a user-defined alias to the version string it stands for.
+///
+/// Aliases are resolved a single level deep, an alias pointing at another alias name is not followed.
+///
+/// # Arguments
+///
+/// * `version` - The version string or alias name.
+/// * `config` - The configuration to read `aliases` from.
+///
+/// # Returns
+///
+/// * `&str` - Returns the aliased version string, or `version` itself if it is not an alias.
+///
+/// # Example
+///
+/// ```rust
+/// let config = Config::default();
+/// assert_eq!(resolve_alias("nightly", &config), "nightly");
+/// ```
+pub fn resolve_alias<'a>(version: &'a str, config: &'a Config) -> &'a str {
+    match config
+        .aliases
+        .as_ref()
+        .and_then(|aliases| aliases.get(version))
+    {
+        Some(target) => {
+            info!("Resolved alias {version} to {target}");
+            target
+        }
+        None => version,
+    }
+}
+
+/// Parses the number of releases to step back from a `stable~N` or `latest~N` specifier.
+///
+/// # Arguments
+///
+/// * `version` - The version string to parse.
+///
+/// # Returns
+///
+/// * `Option<usize>` - Returns `N`, or `None` if the string is not a relative stable specifier.
+///
+/// # Example
+///
+/// ```rust
+/// assert_eq!(parse_stable_offset("stable~2"), Some(2));
+/// assert_eq!(parse_stable_offset("stable"), None);
+/// ```
+fn parse_stable_offset(version: &str) -> Option<usize> {
+    version
+        .strip_prefix("stable~")
+        .or_else(|| version.strip_prefix("latest~"))?
+        .parse()
+        .ok()
+}
+
+/// Finds the release `steps` releases before `stable` in a list of tag names.
+///
+/// Tags that are not releases (`nightly`, `stable`, pre-releases) are ignored and the remaining releases are ordered by their semantic version, so the order in which GitHub returns them does not matter.
+///
+/// # Arguments
+///
+/// * `tags` - The tag names to search.
+/// * `stable` - The tag name of the latest stable release.
+/// * `steps` - How many releases to step back from `stable`.
+///
+/// # Returns
+///
+/// * `Option<String>` - Returns the tag name of the found release, or `None` if `stable` is not among the tags or there are not enough older releases.
+///
+/// # Example
+///
+/// ```rust
+/// let tags = ["v0.10.0", "v0.10.1", "nightly", "v0.9.5"].map(String::from);
+/// assert_eq!(release_before(tags, "v0.10.1", 2).as_deref(), Some("v0.9.5"));
+/// ```
+fn release_before(
+    tags: impl IntoIterator<Item = String>,
+    stable: &str,
+    steps: usize,
+) -> Option<String> {
+    let mut releases: Vec<(Version, String)> = tags
+        .into_iter()
+        .filter_map(|tag| {
+            let semver = Version::parse(tag.strip_prefix('v')?).ok()?;
+            semver.pre.is_empty().then_some((semver, tag))
+        })
+        .collect();
+
+    releases.sort_by(|(a, _), (b, _)| b.cmp(a));
+    releases.dedup_by(|(a, _), (b, _)| a == b);
+
+    let position = releases.iter().position(|(_, tag)| tag == stable)?;
+    releases
+        .into_iter()
+        .nth(position + steps)
+        .map(|(_, tag)| tag)
+}
+
+/// Parses a partial version or a semver requirement string.
+///
+/// Exact versions such as `0.10.0` or `v0.10.0` are not requirements and yield `None`, so they keep resolving to exactly that tag.
+/// A bare partial version such as `0.10` follows cargo's semantics and is treated as `^0.10`.
+///
+/// # Arguments
+///
+/// * `version` - The version string to parse.
+///
+/// # Returns
+///
+/// * `Option<VersionReq>` - Returns the parsed requirement, or `None` if the string is an exact version or not a requirement at all.
+///
+/// # Example
+///
+/// ```rust
+/// assert!(parse_version_req("~0.9").is_some());
+/// assert!(parse_version_req("0.10.0").is_none());
+/// ```
+fn parse_version_req(version: &str) -> Option<VersionReq> {
+    let stripped = version.replace('v', "");
+
+    if Version::parse(&stripped).is_ok() {
+        return None;
+    }
+
+    let is_operator = stripped.starts_with(['^', '~', '>', '<', '=']);
+    if !is_operator && !crate::VERSION_REGEX.is_match(version) {
+        return None;
+    }
+
+    VersionReq::parse(&stripped).ok()
+}
+
+/// Resolves a semver requirement to the tag name of a matching release.
+///
+/// Installed releases are checked first so that a pinned release line keeps working without network access. Only when none of them matches, the newest matching tag from GitHub is used.
+///
+/// # Arguments
+///
+/// * `client` - The client to use for fetching the upstream tags.
+/// * `requirement` - The requirement to match releases against.
+/// * `config` - The configuration to retrieve the downloads directory from.
+///
+/// # Returns
+///
+/// * `Result<String>` - Returns the tag name of the newest matching release, or an error if no release matches.
+///
+/// # Example
+///
+/// ```rust
+/// let requirement = VersionReq::parse("~0.9").unwrap();
+/// let tag_name = resolve_version_req(&client, &requirement, &config).await?;
+/// ```
+async fn resolve_version_req(
+    client: &Client,
+    requirement: &VersionReq,
+    config: &Config,
+) -> Result<String> {
+    let installed = get_installed_releases(config).await?;
+    if let Some((_, tag_name)) = newest_matching(installed, requirement) {
+        return Ok(tag_name);
+    }
+
+    let upstream = get_upstream_tags(client)
+        .await?
+        .into_iter()
+        .filter_map(|tag| {
+            let semver = Version::parse(tag.name.strip_prefix('v')?).ok()?;
+            Some((semver, tag.name))
+        });
+
+    newest_matching(upstream, requirement)
+        .map(|(_, tag_name)| tag_name)
+        .ok_or_else(|| anyhow!("No Neovim release matches {requirement}"))
+}
+
+/// Returns the newest release in `releases` that matches `requirement`.
+fn newest_matching(
+    releases: impl IntoIterator<Item = (Version, String)>,
+    requirement: &VersionReq,
+) -> Option<(Version, String)> {
+    releases
+        .into_iter()
+        .filter(|(semver, _)| requirement.matches(semver))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+}
+
 /// Retrieves the location of the version sync file.
 ///
 /// This function checks the `version_sync_file_location` field of the provided configuration. If the field is `Some`, it checks if a file exists at the specified path. If the file does not exist, it creates a new file at the path. If the field is `None`, it returns `None`.
@@ -235,6 +789,248 @@ pub async fn get_current_version(config: &Config) -> Result<String> {
         .map_err(|_| anyhow!("The used file required for bob could not be found. This could mean that Neovim is not installed through bob."))
 }
 
+/// Retrieves the version of Neovim that was used before the current one.
+///
+/// This function reads the "previous" file from the downloads directory, which is written every time `bob use` or `bob rollback` switches away from a version.
+///
+/// # Arguments
+///
+/// * `config` - The configuration to retrieve the downloads directory from.
+///
+/// # Returns
+///
+/// * `Result<String>` - Returns a `Result` that contains the previously used version, or an error if the operation failed.
+///
+/// # Errors
+///
+/// This function will return an error if:
+///
+/// * The downloads directory cannot be retrieved.
+/// * The "previous" file cannot be read, meaning bob never switched versions.
+///
+/// # Example
+///
+/// ```rust
+/// let config = Config::default();
+/// let previous_version = get_previous_version(&config).await.unwrap();
+/// println!("The previous version is {}", previous_version);
+/// ```
+pub async fn get_previous_version(config: &Config) -> Result<String> {
+    let mut downloads_dir = directories::get_downloads_directory(config).await?;
+    downloads_dir.push("previous");
+    fs::read_to_string(&downloads_dir)
+        .await
+        .map_err(|_| anyhow!("There is no previously used version to switch back to."))
+}
+
+/// Finds the closest per-project version file.
+///
+/// This function walks up from `start` towards the filesystem root and returns the first `.nvim-version` or `.bob-version` file it encounters. When both exist in the same directory, `.nvim-version` takes precedence.
+///
+/// # Arguments
+///
+/// * `start` - The directory to start searching from, usually the current working directory.
+///
+/// # Returns
+///
+/// * `Option<PathBuf>` - Returns the path to the version file, or `None` if no directory on the way up contains one.
+///
+/// # Example
+///
+/// ```rust
+/// let current_dir = std::env::current_dir().unwrap();
+/// if let Some(file) = find_project_version_file(&current_dir) {
+///     println!("Found version file at {}", file.display());
+/// }
+/// ```
+pub fn find_project_version_file(start: &Path) -> Option<PathBuf> {
+    start.ancestors().find_map(|directory| {
+        crate::PROJECT_VERSION_FILES
+            .iter()
+            .map(|name| directory.join(name))
+            .find(|path| path.is_file())
+    })
+}
+
+/// Retrieves the version pinned by the closest per-project version file.
+///
+/// This function looks for a `.nvim-version` or `.bob-version` file starting from the current directory and resolves its content to the name of an installed version directory, without touching the network.
+/// Aliases are resolved first, the rest is resolved by `resolve_project_version`.
+///
+/// # Arguments
+///
+/// * `config` - The configuration to retrieve the downloads directory from.
+///
+/// # Returns
+///
+/// * `Result<Option<String>>` - Returns a `Result` that contains the pinned version, `None` if there is no version file, or an error if the file could not be read or resolved.
+///
+/// # Errors
+///
+/// This function will return an error if:
+///
+/// * The current directory cannot be determined.
+/// * The version file cannot be read or is empty.
+/// * The pinned version cannot be resolved offline.
+///
+/// # Example
+///
+/// ```rust
+/// let config = Config::default();
+/// if let Some(version) = get_project_version(&config).await.unwrap() {
+///     println!("This project uses {version}");
+/// }
+/// ```
+pub async fn get_project_version(config: &Config) -> Result<Option<String>> {
+    let current_dir = std::env::current_dir()?;
+    let Some(version_file) = find_project_version_file(&current_dir) else {
+        return Ok(None);
+    };
+
+    let content = fs::read_to_string(&version_file)
+        .await
+        .with_context(|| format!("Failed to read {}", version_file.display()))?;
+    let version = resolve_alias(content.trim(), config);
+
+    if version.is_empty() {
+        return Err(anyhow!("Version file {} is empty", version_file.display()));
+    }
+
+    let resolved = resolve_project_version(version, config)
+        .await
+        .with_context(|| format!("Failed to resolve {}", version_file.display()))?;
+
+    Ok(Some(resolved))
+}
+
+/// Resolves the content of a project version file to the name of an installed version directory.
+///
+/// The proxy runs on every start of Neovim, so nothing here touches the network. `stable` and `latest` resolve to the installed stable release, `previous` to the previously used version,
+/// partial versions and semver ranges to the newest installed release matching them, git sources to the name of their build, `[v]x.x.x` is normalized to its tag name, commit hashes are shortened to the name of their directory and `nightly`, rollbacks and custom names are used as is.
+///
+/// # Arguments
+///
+/// * `version` - The pinned version, with aliases already resolved.
+/// * `config` - The configuration to retrieve the downloads directory from.
+///
+/// # Returns
+///
+/// * `Result<String>` - Returns the name of the version directory, or an error if the version cannot be resolved offline.
+///
+/// # Errors
+///
+/// This function will return an error if:
+///
+/// * `version` asks for `stable` but no release is installed.
+/// * `version` is a partial version or range no installed release matches.
+/// * `version` asks for `previous` but there is no previously used version.
+/// * `version` needs the network to be resolved, like `head` or `stable~1`.
+/// * `version` is a malformed git source.
+/// * `version` is not a valid version string.
+///
+/// # Example
+///
+/// ```rust
+/// let config = Config::default();
+/// assert_eq!(resolve_project_version("0.10.0", &config).await?, "v0.10.0");
+/// ```
+async fn resolve_project_version(version: &str, config: &Config) -> Result<String> {
+    match version {
+        "stable" | "latest" => metadata::get_recorded_stable(config).await?.ok_or_else(|| {
+            anyhow!("No release is installed for {version}. Install it with: bob install stable")
+        }),
+        "nightly" => Ok(version.to_string()),
+        "previous" => {
+            let previous_version = get_previous_version(config).await?;
+            Box::pin(resolve_project_version(previous_version.trim(), config)).await
+        }
+        "head" | "git" | "HEAD" => Err(anyhow!(
+            "{version} needs network access to find the latest commit, pin a commit hash instead"
+        )),
+        _ => {
+            if let Some(source) = parse_git_source(version, config) {
+                return Ok(source?.name);
+            }
+
+            if parse_stable_offset(version).is_some() {
+                return Err(anyhow!(
+                    "{version} needs network access to list the releases, pin a release such as 0.10 instead"
+                ));
+            }
+
+            if let Some(requirement) = parse_version_req(version) {
+                let installed = get_installed_releases(config).await?;
+                return newest_matching(installed, &requirement)
+                    .map(|(_, tag_name)| tag_name)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "No installed release matches {version}. Install one with: bob install {version}"
+                        )
+                    });
+            }
+
+            if crate::VERSION_REGEX.is_match(version) {
+                if version.starts_with('v') {
+                    return Ok(version.to_string());
+                }
+                return Ok(format!("v{version}"));
+            }
+
+            if is_hash(version) {
+                return Ok(version.chars().take(7).collect());
+            }
+
+            if crate::NIGHTLY_REGEX.is_match(version) || is_valid_install_name(version) {
+                return Ok(version.to_string());
+            }
+
+            Err(anyhow!("{version} is not a valid version string"))
+        }
+    }
+}
+
+/// Lists the installed releases as pairs of their semantic version and directory name.
+///
+/// Nightly, rollback and commit builds are skipped since they are not named after a release.
+///
+/// # Arguments
+///
+/// * `config` - The configuration to retrieve the downloads directory from.
+///
+/// # Returns
+///
+/// * `Result<Vec<(Version, String)>>` - Returns the installed releases in no particular order, or an error if the downloads directory cannot be read.
+///
+/// # Example
+///
+/// ```rust
+/// let config = Config::default();
+/// for (semver, tag_name) in get_installed_releases(&config).await? {
+///     println!("{tag_name} is installed ({semver})");
+/// }
+/// ```
+pub async fn get_installed_releases(config: &Config) -> Result<Vec<(Version, String)>> {
+    let downloads_dir = directories::get_downloads_directory(config).await?;
+    let mut dir = fs::read_dir(&downloads_dir).await?;
+    let mut releases = Vec::new();
+
+    while let Some(entry) = dir.next_entry().await? {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        let Some(semver) = name
+            .strip_prefix('v')
+            .and_then(|stripped| Version::parse(stripped).ok())
+        else {
+            continue;
+        };
+
+        releases.push((semver, name));
+    }
+
+    Ok(releases)
+}
+
 /// Checks if a specific version is currently being used.
 ///
 /// This function retrieves the current version from the configuration and checks if it matches the specified version.
@@ -290,6 +1086,8 @@ pub async fn is_version_used(version: &str, config: &Config) -> bool {
 /// println!("The latest commit is {}", latest_commit);
 /// ```
 async fn get_latest_commit(client: &Client) -> Result<String> {
+    ensure_online("Fetching the latest commit")?;
+
     let response = client
         .get("https://api.github.com/repos/neovim/neovim/commits/master")
         .header("user-agent", "bob")
@@ -305,11 +1103,311 @@ async fn get_latest_commit(client: &Client) -> Result<String> {
 }
 
 #[cfg(test)]
-mod version_is_hash_tests {
+mod project_version_file_tests {
+    use super::find_project_version_file;
+    use std::fs;
+
+    #[test]
+    fn finds_closest_version_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let nested = root.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+
+        assert_eq!(find_project_version_file(&nested), None);
+
+        fs::write(root.join(".bob-version"), "nightly").unwrap();
+        assert_eq!(
+            find_project_version_file(&nested),
+            Some(root.join(".bob-version"))
+        );
+
+        fs::write(root.join(".nvim-version"), "0.10.0").unwrap();
+        assert_eq!(
+            find_project_version_file(&nested),
+            Some(root.join(".nvim-version"))
+        );
+
+        fs::write(root.join("a").join(".bob-version"), "stable").unwrap();
+        assert_eq!(
+            find_project_version_file(&nested),
+            Some(root.join("a").join(".bob-version"))
+        );
+    }
+}
+
+#[cfg(test)]
+mod project_version_tests {
+    use super::resolve_project_version;
+    use crate::config::Config;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn downloads_with(installed: &[&str]) -> (TempDir, Config) {
+        let temp = tempfile::tempdir().unwrap();
+        for name in installed {
+            fs::create_dir_all(temp.path().join(name)).unwrap();
+        }
+        let config = Config {
+            downloads_location: Some(temp.path().to_string_lossy().to_string()),
+            ..Config::default()
+        };
+        (temp, config)
+    }
+
+    #[tokio::test]
+    async fn resolves_pinned_names_offline() {
+        let (_temp, config) = downloads_with(&["v0.9.5", "v0.10.4", "nightly"]);
+
+        for (pinned, expected) in [
+            ("stable", "v0.10.4"),
+            ("latest", "v0.10.4"),
+            ("0.9.5", "v0.9.5"),
+            ("v0.9.5", "v0.9.5"),
+            ("nightly", "nightly"),
+            ("nightly-abcdef1", "nightly-abcdef1"),
+            ("a1b2c3d4e5f60718293a4b5c6d7e8f9012345678", "a1b2c3d"),
+            ("ci-build", "ci-build"),
+        ] {
+            assert_eq!(
+                resolve_project_version(pinned, &config).await.unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_requirements_against_installed_releases() {
+        let (_temp, config) = downloads_with(&["v0.9.4", "v0.9.5", "v0.10.0", "v0.10.4"]);
+
+        for (pinned, expected) in [
+            ("0.10", "v0.10.4"),
+            ("v0.9", "v0.9.5"),
+            ("~0.9", "v0.9.5"),
+            (">=0.9.5, <0.10", "v0.9.5"),
+            ("^0.10.1", "v0.10.4"),
+        ] {
+            assert_eq!(
+                resolve_project_version(pinned, &config).await.unwrap(),
+                expected
+            );
+        }
+
+        let error = resolve_project_version("0.11", &config).await.unwrap_err();
+        assert!(error.to_string().contains("bob install 0.11"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn resolves_git_sources_to_their_build() {
+        let (_temp, config) = downloads_with(&[]);
+
+        for (pinned, expected) in [
+            ("pr:123", "pr-123"),
+            ("github:user/neovim@fix", "user-neovim-fix"),
+            (
+                "git+https://git.example.com/neovim.git@fixes",
+                "neovim-fixes",
+            ),
+        ] {
+            assert_eq!(
+                resolve_project_version(pinned, &config).await.unwrap(),
+                expected
+            );
+        }
+
+        assert!(resolve_project_version("pr:abc", &config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn resolves_previous_to_the_previously_used_version() {
+        let (temp, config) = downloads_with(&["v0.9.5", "v0.10.4"]);
+        assert!(resolve_project_version("previous", &config).await.is_err());
+
+        fs::write(temp.path().join("previous"), "v0.9.5\n").unwrap();
+        assert_eq!(
+            resolve_project_version("previous", &config).await.unwrap(),
+            "v0.9.5"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_versions_that_need_the_network() {
+        let (_temp, config) = downloads_with(&[]);
+
+        let error = resolve_project_version("stable", &config)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("bob install stable"));
+
+        for pinned in ["head", "HEAD", "git", "stable~1", "latest~2"] {
+            let error = resolve_project_version(pinned, &config).await.unwrap_err();
+            assert!(error.to_string().contains("network"), "{error}");
+        }
+
+        assert!(
+            resolve_project_version("not a version", &config)
+                .await
+                .is_err()
+        );
+    }
+}
+
+#[cfg(test)]
+mod version_req_tests {
+    use super::{newest_matching, parse_version_req};
+    use semver::Version;
+
+    #[test]
+    fn exact_versions_are_not_requirements() {
+        assert!(parse_version_req("0.10.0").is_none());
+        assert!(parse_version_req("v0.9.5").is_none());
+        assert!(parse_version_req("nightly").is_none());
+        assert!(parse_version_req("abcdef1").is_none());
+        assert!(parse_version_req("1234567").is_none());
+    }
+
+    #[test]
+    fn partial_versions_and_ranges_are_requirements() {
+        for case in [
+            "0.10",
+            "v0.10",
+            "~0.9",
+            "^0.10",
+            ">=0.9.5",
+            ">=0.10, <0.11",
+            "0.9.x",
+        ] {
+            assert!(parse_version_req(case).is_some(), "{case} should parse");
+        }
+    }
+
+    #[test]
+    fn picks_newest_matching_release() {
+        let releases = ["v0.9.4", "v0.9.5", "v0.10.0", "v0.10.4", "v0.11.0"]
+            .map(|tag| (Version::parse(&tag[1..]).unwrap(), tag.to_string()));
+
+        let cases = [
+            ("0.10", Some("v0.10.4")),
+            ("~0.9", Some("v0.9.5")),
+            (">=0.9.5, <0.10.4", Some("v0.10.0")),
+            (">=0.11.1", None),
+        ];
+
+        for (case, expected) in cases {
+            let requirement = parse_version_req(case).unwrap();
+            let found = newest_matching(releases.clone(), &requirement).map(|(_, tag)| tag);
+            assert_eq!(found.as_deref(), expected, "{case}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod relative_version_tests {
+    use super::{parse_stable_offset, release_before};
 
-    pub(crate) fn is_hash(version: &str) -> bool {
-        crate::HASH_REGEX.is_match(version)
+    #[test]
+    fn parses_stable_offsets() {
+        assert_eq!(parse_stable_offset("stable~1"), Some(1));
+        assert_eq!(parse_stable_offset("latest~3"), Some(3));
+        assert_eq!(parse_stable_offset("stable~"), None);
+        assert_eq!(parse_stable_offset("stable~x"), None);
+        assert_eq!(parse_stable_offset("stable"), None);
+        assert_eq!(parse_stable_offset("nightly~1"), None);
+    }
+
+    #[test]
+    fn steps_back_from_stable() {
+        let tags = [
+            "nightly",
+            "stable",
+            "v0.11.0",
+            "v0.10.4",
+            "v0.10.3",
+            "v0.9.5",
+            "v0.11.1-rc1",
+        ]
+        .map(String::from);
+
+        assert_eq!(
+            release_before(tags.clone(), "v0.11.0", 0).as_deref(),
+            Some("v0.11.0")
+        );
+        assert_eq!(
+            release_before(tags.clone(), "v0.11.0", 1).as_deref(),
+            Some("v0.10.4")
+        );
+        assert_eq!(
+            release_before(tags.clone(), "v0.10.4", 2).as_deref(),
+            Some("v0.9.5")
+        );
+        assert_eq!(release_before(tags.clone(), "v0.11.0", 4), None);
+        assert_eq!(release_before(tags, "v0.12.0", 1), None);
     }
+}
+
+#[cfg(test)]
+mod alias_tests {
+    use super::{is_version_specifier, resolve_alias};
+    use crate::config::Config;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn version_specifiers_cannot_be_alias_names() {
+        for case in [
+            "nightly", "stable", "previous", "stable~1", "0.10", "v0.9.5", "abcdef1",
+        ] {
+            assert!(is_version_specifier(case), "{case}");
+        }
+        for case in ["work", "plugin-min", "ci"] {
+            assert!(!is_version_specifier(case), "{case}");
+        }
+    }
+
+    #[test]
+    fn resolves_aliases_one_level_deep() {
+        let config = Config {
+            aliases: Some(BTreeMap::from([
+                ("work".to_string(), "v0.10.0".to_string()),
+                ("daily".to_string(), "work".to_string()),
+            ])),
+            ..Config::default()
+        };
+
+        assert_eq!(resolve_alias("work", &config), "v0.10.0");
+        assert_eq!(resolve_alias("daily", &config), "work");
+        assert_eq!(resolve_alias("nightly", &config), "nightly");
+    }
+}
+
+#[cfg(test)]
+mod install_name_tests {
+    use super::is_valid_install_name;
+
+    #[test]
+    fn custom_install_names_are_validated() {
+        for case in ["ci-build", "pr_1234", "pr-12345", "release.candidate"] {
+            assert!(is_valid_install_name(case), "{case}");
+        }
+        for case in [
+            "",
+            "-build",
+            "../escape",
+            "ci build",
+            "nvim-bin",
+            "neovim-git",
+            "stable",
+            "v0.10.0",
+            "abcdef1",
+            "platforms",
+        ] {
+            assert!(!is_valid_install_name(case), "{case}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod version_is_hash_tests {
+    use super::is_hash;
 
     #[test]
     fn test_is_hash() {
@@ -320,6 +1418,8 @@ mod version_is_hash_tests {
             ("xyz123", false),
             ("abc1", false),
             ("abc123abc123abc123abc123abc123abc123abc123", false),
+            ("pr-12345", false),
+            ("nightly-abcdef1", false),
         ];
 
         version_expected
@@ -371,3 +1471,65 @@ mod version_is_hash_tests {
         assert!(!is_hash(version));
     }
 }
+
+#[cfg(test)]
+mod git_source_tests {
+    use super::*;
+
+    fn parse(version: &str) -> GitSource {
+        parse_git_source(version, &Config::default())
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn git_sources_are_parsed_and_named() {
+        assert_eq!(
+            parse("github:user/neovim@feature/lsp"),
+            GitSource {
+                remote: "https://github.com/user/neovim.git".to_string(),
+                git_ref: "feature/lsp".to_string(),
+                name: "user-neovim-feature-lsp".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("pr:12345"),
+            GitSource {
+                remote: "https://github.com/neovim/neovim.git".to_string(),
+                git_ref: "refs/pull/12345/head".to_string(),
+                name: "pr-12345".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("git+ssh://git@git.example.com/editors/neovim.git@fixes"),
+            GitSource {
+                remote: "ssh://git@git.example.com/editors/neovim.git".to_string(),
+                git_ref: "fixes".to_string(),
+                name: "neovim-fixes".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("git+https://git.example.com/neovim.git").git_ref,
+            "HEAD"
+        );
+
+        let config = Config {
+            git_remote: Some("https://mirror.example.com/neovim.git".to_string()),
+            ..Config::default()
+        };
+        assert_eq!(
+            parse_git_source("pr:1", &config).unwrap().unwrap().remote,
+            "https://mirror.example.com/neovim.git"
+        );
+    }
+
+    #[test]
+    fn other_versions_and_malformed_sources_are_rejected() {
+        let config = Config::default();
+        assert!(parse_git_source("v0.10.0", &config).is_none());
+        assert!(parse_git_source("ci-build", &config).is_none());
+        assert!(parse_git_source("pr:abc", &config).unwrap().is_err());
+        assert!(parse_git_source("github:neovim", &config).unwrap().is_err());
+        assert!(parse_git_source("git+", &config).unwrap().is_err());
+    }
+}
@@ -1,4 +1,6 @@
+use chrono::{DateTime, Utc};
 use semver::Version;
+use serde::{Deserialize, Serialize};
 
 use crate::github_requests::UpstreamVersion;
 use std::path::PathBuf;
@@ -43,6 +45,8 @@ pub struct ParsedVersion {
 /// * `Nightly` - Represents a nightly version.
 /// * `Hash` - Represents a version identified by a hash.
 /// * `NightlyRollback` - Represents a nightly version that has been rolled back.
+/// * `Local` - Represents a version installed under a custom name, for example from a local archive.
+/// * `Git` - Represents a build of a branch, pull request or ref of a git repository, see `GitSource`.
 ///
 /// # Example
 ///
@@ -54,6 +58,8 @@ pub struct ParsedVersion {
 ///     VersionType::Nightly => println!("This is a nightly version."),
 ///     VersionType::Hash => println!("This is a version identified by a hash."),
 ///     VersionType::NightlyRollback => println!("This is a nightly version that has been rolled back."),
+///     VersionType::Local => println!("This is a version installed under a custom name."),
+///     VersionType::Git => println!("This is a build of a git ref."),
 /// }
 /// ```
 #[derive(PartialEq, Eq, Debug)]
@@ -63,6 +69,34 @@ pub enum VersionType {
     Nightly,
     Hash,
     NightlyRollback,
+    Local,
+    Git,
+}
+
+/// Represents a git repository and ref to build Neovim from.
+///
+/// Sources are given as `github:<owner>/<repo>[@<ref>]`, `pr:<number>` or `git+<url>[@<ref>]`, see `parse_git_source`.
+///
+/// # Fields
+///
+/// * `remote: String` - The URL of the repository.
+/// * `git_ref: String` - The branch, tag, commit or other ref to build.
+/// * `name: String` - The name the build is installed as.
+///
+/// # Example
+///
+/// ```rust
+/// let source = GitSource {
+///     remote: "https://github.com/user/neovim.git".to_string(),
+///     git_ref: "my-branch".to_string(),
+///     name: "user-neovim-my-branch".to_string(),
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitSource {
+    pub remote: String,
+    pub git_ref: String,
+    pub name: String,
 }
 
 /// Represents a local nightly version of the software.
@@ -121,3 +155,128 @@ pub struct LocalVersion {
     pub path: String,
     pub semver: Option<Version>,
 }
+
+/// Represents the metadata bob records about an installed version.
+///
+/// This struct is written to `bob-metadata.json` inside the version's directory at install time, so installed versions can be resolved later without asking GitHub.
+///
+/// # Fields
+///
+/// * `tag_name: String` - The tag name the version was installed under.
+/// * `stable: bool` - Whether the version was installed as the latest stable release.
+/// * `installed_at: DateTime<Utc>` - The date and time the version was installed.
+/// * `release: Option<UpstreamVersion>` - The upstream release data, when it was known at install time.
+/// * `source: Option<InstallSource>` - Where the version came from, when it wasn't downloaded from the Neovim releases.
+///
+/// # Example
+///
+/// ```rust
+/// let metadata = InstallMetadata {
+///     tag_name: "v0.10.0".to_string(),
+///     stable: true,
+///     installed_at: Utc::now(),
+///     release: None,
+///     source: None,
+/// };
+/// println!("The install metadata is {:?}", metadata);
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InstallMetadata {
+    pub tag_name: String,
+    #[serde(default)]
+    pub stable: bool,
+    pub installed_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release: Option<UpstreamVersion>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<InstallSource>,
+}
+
+/// Represents where an installed version came from, when it wasn't downloaded from the Neovim releases.
+///
+/// # Variants
+///
+/// * `File { path }` - The version was installed from a local archive.
+/// * `Url { url }` - The version was installed from an archive at an arbitrary URL.
+/// * `Build { remote, git_ref, patches, profile }` - The version was built from source, `git_ref` is the ref that was checked out from `remote`, `patches` were applied to it and it was built with the build profile `profile`.
+/// * `Checkout { path }` - The version was built from the working tree of a local Neovim checkout.
+///
+/// # Example
+///
+/// ```rust
+/// let source = InstallSource::File { path: "/mnt/usb/nvim-linux-x86_64.tar.gz".to_string() };
+/// println!("{source}");
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum InstallSource {
+    File {
+        path: String,
+    },
+    Url {
+        url: String,
+    },
+    Build {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        remote: Option<String>,
+        git_ref: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        patches: Vec<AppliedPatch>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        profile: Option<String>,
+    },
+    Checkout {
+        path: String,
+    },
+}
+
+impl std::fmt::Display for InstallSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallSource::File { path } => write!(f, "file {path}"),
+            InstallSource::Url { url } => write!(f, "{url}"),
+            InstallSource::Build {
+                remote,
+                git_ref,
+                patches,
+                profile,
+            } => {
+                match remote {
+                    Some(remote) => write!(f, "{git_ref} from {remote}")?,
+                    None => write!(f, "source build of {git_ref}")?,
+                }
+                match patches.len() {
+                    0 => (),
+                    1 => write!(f, " with 1 patch")?,
+                    count => write!(f, " with {count} patches")?,
+                }
+                match profile {
+                    Some(profile) => write!(f, " ({profile} profile)"),
+                    None => Ok(()),
+                }
+            }
+            InstallSource::Checkout { path } => write!(f, "checkout {path}"),
+        }
+    }
+}
+
+/// Represents a patch file that was applied to the source of a build.
+///
+/// # Fields
+///
+/// * `path: String` - The path of the patch file.
+/// * `sha256: String` - The hex encoded SHA-256 of the patch file at the time it was applied.
+///
+/// # Example
+///
+/// ```rust
+/// let patch = AppliedPatch {
+///     path: "/home/user/patches/fix.diff".to_string(),
+///     sha256: "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".to_string(),
+/// };
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AppliedPatch {
+    pub path: String,
+    pub sha256: String,
+}
@@ -12,7 +12,7 @@ use std::{env, process::exit};
 use tracing::{Level, error, warn};
 
 pub(crate) use crate::consts::{
-    ENVIRONMENT_VAR_REGEX, FILETYPE_EXT, HASH_REGEX, NIGHTLY_REGEX, VERSION_REGEX,
+    ENVIRONMENT_VAR_REGEX, HASH_REGEX, NIGHTLY_REGEX, PROJECT_VERSION_FILES, VERSION_REGEX,
 };
 
 #[tokio::main]